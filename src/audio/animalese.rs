@@ -0,0 +1,194 @@
+use std::ops::Range;
+
+use super::voice::{Beep, Envelope};
+
+// "Animalese" style speech: instead of a beep per letter, each syllable is
+// one vowel-colored blip.  Letters still show one at a time; the blip just
+// runs straight through them.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Syllables {
+    // pitch lost over the course of each blip
+    pub glide: f32,
+    // width of each formant peak, as a fraction of its frequency
+    pub formant_bandwidth: f64,
+}
+
+impl Default for Syllables {
+    fn default() -> Self {
+        Syllables {
+            glide: 0.1,
+            formant_bandwidth: 0.12,
+        }
+    }
+}
+
+fn is_vowel(c: char, word_start: bool) -> bool {
+    match c.to_lowercase().next().unwrap_or(c) {
+        'a' | 'e' | 'i' | 'o' | 'u' => true,
+        'y' => !word_start,
+        _ => false,
+    }
+}
+
+// (F1, F2) in hz for a vowel, roughly an adult speaker's
+pub fn vowel_formants(c: char) -> (f64, f64) {
+    match c.to_lowercase().next().unwrap_or(c) {
+        'a' => (730.0, 1090.0),
+        'e' => (530.0, 1840.0),
+        'i' | 'y' => (270.0, 2290.0),
+        'o' => (570.0, 840.0),
+        'u' => (300.0, 870.0),
+        // a neutral schwa
+        _ => (500.0, 1500.0),
+    }
+}
+
+// Splits each run of letters into syllables, breaking before a consonant
+// that leads into a vowel: "hello" is "hel" "lo", "testing" is "tes" "ting".
+pub fn syllables(chars: &[char]) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_alphabetic() {
+            i += 1;
+            continue;
+        }
+        let word_start = i;
+        let mut start = i;
+        let mut seen_vowel = false;
+        while i < chars.len() && chars[i].is_alphabetic() {
+            let vowel = is_vowel(chars[i], i == word_start);
+            let next_vowel = chars.get(i + 1)
+                .map(|&n| n.is_alphabetic() && is_vowel(n, false))
+                .unwrap_or(false);
+            if !vowel && next_vowel && seen_vowel {
+                found.push(start..i);
+                start = i;
+                seen_vowel = false;
+            }
+            seen_vowel = seen_vowel || vowel;
+            i += 1;
+        }
+        found.push(start..i);
+    }
+    found
+}
+
+impl Syllables {
+    // Turns per-letter beeps into per-syllable blips.  Beeps within a
+    // syllable share one formant tone, fade only at the syllable's edges and
+    // keep no gaps between them, so they play as one continuous sound.  A
+    // markup pause inside a syllable splits it.
+    pub fn apply(&self, chars: &[char], beeps: &mut [Beep]) {
+        for range in syllables(chars) {
+            let mut start = range.start;
+            for i in range.clone() {
+                if i > start && beeps[i].lead_millis > 0 {
+                    self.join(chars, &mut beeps[start..i], start);
+                    start = i;
+                }
+            }
+            self.join(chars, &mut beeps[start..range.end], start);
+        }
+    }
+
+    fn join(&self, chars: &[char], beeps: &mut [Beep], offset: usize) {
+        let n = beeps.len();
+        if n == 0 {
+            return;
+        }
+        let vowel = (offset..offset + n)
+            .map(|i| chars[i])
+            .find(|&c| is_vowel(c, false))
+            .unwrap_or(' ');
+        let (f1, f2) = vowel_formants(vowel);
+        let chz = beeps[0].chz;
+        let volume = beeps[0].volume;
+        let envelope = beeps[0].envelope;
+        for (k, b) in beeps.iter_mut().enumerate() {
+            let along = k as f32 / n as f32;
+            b.chz = (chz as f32 * (1.0 - self.glide * along)) as usize;
+            b.volume = volume;
+            b.formants = Some([(f1, f1 * self.formant_bandwidth),
+                               (f2, f2 * self.formant_bandwidth)]);
+            b.envelope = Envelope {
+                attack_millis: if k == 0 { envelope.attack_millis } else { 0 },
+                release_millis: if k == n - 1 { envelope.release_millis } else { 0 },
+            };
+            if k < n - 1 {
+                b.millis += b.gap_millis;
+                b.gap_millis = 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{syllables, vowel_formants, Syllables};
+    use audio::wave::Waveform;
+    use audio::voice::{Beep, Envelope};
+
+    fn split(text: &str) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        syllables(&chars).into_iter().map(|r| chars[r].iter().collect()).collect()
+    }
+
+    #[test]
+    fn splits_before_a_consonant_leading_into_a_vowel() {
+        assert_eq!(split("hello"), vec!["hel", "lo"]);
+        assert_eq!(split("testing"), vec!["tes", "ting"]);
+        assert_eq!(split("banana"), vec!["ba", "na", "na"]);
+        // y is a consonant at the start of a word and a vowel after
+        assert_eq!(split("yes"), vec!["yes"]);
+        assert_eq!(split("rhythm"), vec!["rhythm"]);
+        assert_eq!(split("happy"), vec!["hap", "py"]);
+        assert_eq!(split("HELLO"), vec!["HEL", "LO"]);
+    }
+
+    #[test]
+    fn skips_what_isnt_a_letter() {
+        assert_eq!(split("ok, 42 hi!"), vec!["ok", "hi"]);
+        assert_eq!(split("can't"), vec!["can", "t"]);
+        assert!(split("... 123 !?").is_empty());
+        assert!(split("").is_empty());
+        // letters with no vowels are still a syllable
+        assert_eq!(split("hmm shh"), vec!["hmm", "shh"]);
+        assert_eq!(split("世界"), vec!["世界"]);
+    }
+
+    fn beep(index: usize, chz: usize) -> Beep {
+        Beep {
+            index,
+            text: String::new(),
+            chz,
+            lead_millis: 0,
+            millis: 100,
+            gap_millis: 10,
+            volume: 1.0,
+            waveform: Waveform::Sine,
+            envelope: Envelope::default(),
+            formants: None,
+        }
+    }
+
+    #[test]
+    fn syllables_blip_as_one() {
+        let chars: Vec<char> = "hmm ok".chars().collect();
+        let mut beeps: Vec<Beep> = (0..chars.len()).map(|i| beep(i, 10_000 + i)).collect();
+        Syllables::default().apply(&chars, &mut beeps);
+        // no vowel to color it, so a schwa
+        let (f1, f2) = vowel_formants(' ');
+        assert_eq!(beeps[0].formants.map(|x| (x[0].0, x[1].0)), Some((f1, f2)));
+        let (f1, f2) = vowel_formants('o');
+        assert_eq!(beeps[5].formants.map(|x| (x[0].0, x[1].0)), Some((f1, f2)));
+        // gapless inside, gliding down from the first beep's pitch
+        assert_eq!((beeps[0].millis, beeps[0].gap_millis), (110, 0));
+        assert_eq!((beeps[2].millis, beeps[2].gap_millis), (100, 10));
+        assert!(beeps[0].chz == 10_000 && beeps[1].chz < 10_000 && beeps[2].chz < beeps[1].chz);
+        assert_eq!((beeps[1].envelope.attack_millis, beeps[1].envelope.release_millis), (0, 0));
+        // the space and anything else outside a word is left be
+        assert!(beeps[3].formants.is_none());
+    }
+}
@@ -0,0 +1,153 @@
+// Per-character tweaks to a Voice: which letters sound higher, longer or
+// quieter.  Rules are checked in order and the first match wins.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CharMap {
+    pub rules: Vec<(CharClass, CharShape)>,
+    // added to the pitch scale of uppercase letters
+    pub uppercase_pitch: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CharClass {
+    // any of these characters, compared lowercased
+    Chars(Vec<char>),
+    Alphabetic,
+    Digit,
+    Punctuation,
+    Any,
+}
+
+// Multipliers on a Voice's pitch, character length and volume.  A pitch of
+// 0.0 makes the character silent.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CharShape {
+    pub pitch: f32,
+    pub duration: f32,
+    pub volume: f32,
+}
+
+impl Default for CharShape {
+    fn default() -> Self {
+        CharShape {
+            pitch: 1.0,
+            duration: 1.0,
+            volume: 1.0,
+        }
+    }
+}
+
+impl CharShape {
+    pub fn pitch(pitch: f32) -> Self {
+        CharShape { pitch, ..CharShape::default() }
+    }
+}
+
+impl Default for CharMap {
+    // the old scratch BeepWriter::choose_scale table
+    fn default() -> Self {
+        CharMap {
+            rules: vec![(CharClass::Digit, CharShape::pitch(0.65)),
+                        (CharClass::Punctuation, CharShape::pitch(0.50)),
+                        (CharClass::chars("tshnp"), CharShape::pitch(0.90)),
+                        (CharClass::chars("aeiou"), CharShape::pitch(0.75)),
+                        (CharClass::chars("qzwyjk"), CharShape::pitch(1.2))],
+            uppercase_pitch: 0.1,
+        }
+    }
+}
+
+impl CharClass {
+    pub fn chars(s: &str) -> Self {
+        CharClass::Chars(s.chars().collect())
+    }
+    pub fn matches(&self, c: char) -> bool {
+        match *self {
+            CharClass::Chars(ref v) => {
+                let lower = c.to_lowercase().next().unwrap_or(c);
+                v.contains(&lower)
+            }
+            CharClass::Alphabetic => c.is_alphabetic(),
+            CharClass::Digit => c.is_numeric(),
+            CharClass::Punctuation => !c.is_alphanumeric() && !c.is_whitespace(),
+            CharClass::Any => true,
+        }
+    }
+}
+
+impl CharMap {
+    // every character sounds the same
+    pub fn flat() -> Self {
+        CharMap {
+            rules: Vec::new(),
+            uppercase_pitch: 0.0,
+        }
+    }
+    pub fn shape(&self, c: char) -> CharShape {
+        let mut shape = self.rules
+            .iter()
+            .find(|(class, _)| class.matches(c))
+            .map(|&(_, shape)| shape)
+            .unwrap_or_default();
+        if c.is_uppercase() && shape.pitch != 0.0 {
+            shape.pitch += self.uppercase_pitch;
+        }
+        shape
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CharClass, CharMap, CharShape};
+
+    fn pitch(map: &CharMap, c: char) -> f32 {
+        map.shape(c).pitch
+    }
+
+    #[test]
+    fn shapes_letters_by_kind() {
+        let map = CharMap::default();
+        for &c in &['a', 'e', 'i', 'o', 'u'] {
+            assert_eq!(pitch(&map, c), 0.75, "{}", c);
+        }
+        assert_eq!(pitch(&map, 't'), 0.90);
+        assert_eq!(pitch(&map, 'z'), 1.2);
+        // consonants with no rule of their own are left be
+        assert_eq!(map.shape('b'), CharShape::default());
+        assert_eq!(pitch(&map, '7'), 0.65);
+        assert_eq!(pitch(&map, '?'), 0.50);
+        // uppercase letters are a touch higher
+        assert!((pitch(&map, 'A') - 0.85).abs() < 1e-6);
+        assert!((pitch(&map, 'B') - 1.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn unmapped_characters_are_default() {
+        let map = CharMap::default();
+        assert_eq!(map.shape(' '), CharShape::default());
+        assert_eq!(map.shape('\n'), CharShape::default());
+        assert_eq!(map.shape('世'), CharShape::default());
+        let flat = CharMap::flat();
+        for c in "aZ9?".chars() {
+            assert_eq!(flat.shape(c), CharShape::default());
+        }
+    }
+
+    #[test]
+    fn first_rule_wins() {
+        let map = CharMap {
+            rules: vec![(CharClass::chars("x"), CharShape::pitch(0.0)),
+                        (CharClass::Alphabetic, CharShape::pitch(2.0)),
+                        (CharClass::Any, CharShape::pitch(0.5))],
+            uppercase_pitch: 0.25,
+        };
+        assert_eq!(pitch(&map, 'y'), 2.0);
+        assert_eq!(pitch(&map, '.'), 0.5);
+        // silent stays silent, uppercase or not
+        assert_eq!(pitch(&map, 'x'), 0.0);
+        assert_eq!(pitch(&map, 'X'), 0.0);
+        assert_eq!(pitch(&map, 'Y'), 2.25);
+    }
+}
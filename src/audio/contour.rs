@@ -0,0 +1,169 @@
+use std::ops::Range;
+
+// Intonation over a whole sentence: questions rise at the end, exclamations
+// are higher and louder, statements trail off.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Contour {
+    // 0.0 is flat, 1.0 the values below as given
+    pub strength: f32,
+    // pitch added by the end of a question...
+    pub question_rise: f32,
+    // ...over this many of its last letters
+    pub question_tail: usize,
+    pub exclaim_pitch: f32,
+    pub exclaim_volume: f32,
+    // pitch lost by the end of a statement
+    pub statement_fall: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SentenceKind {
+    Statement,
+    Question,
+    Exclamation,
+}
+
+impl Default for Contour {
+    fn default() -> Self {
+        Contour {
+            strength: 1.0,
+            question_rise: 0.3,
+            question_tail: 4,
+            exclaim_pitch: 0.15,
+            exclaim_volume: 0.25,
+            statement_fall: 0.1,
+        }
+    }
+}
+
+// Splits text into sentences, each ending after its run of . ! ? …
+pub fn sentences(chars: &[char]) -> Vec<(Range<usize>, SentenceKind)> {
+    let is_end = |c: char| c == '.' || c == '!' || c == '?' || c == '…';
+    let mut found = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        if !is_end(chars[i]) {
+            i += 1;
+            continue;
+        }
+        let mut kind = SentenceKind::Statement;
+        while i < chars.len() && is_end(chars[i]) {
+            match chars[i] {
+                '?' => kind = SentenceKind::Question,
+                '!' if kind != SentenceKind::Question => kind = SentenceKind::Exclamation,
+                _ => {}
+            }
+            i += 1;
+        }
+        if i == chars.len() || chars[i].is_whitespace() {
+            found.push((start..i, kind));
+            start = i;
+        }
+    }
+    if start < chars.len() && chars[start..].iter().any(|c| !c.is_whitespace()) {
+        found.push((start..chars.len(), SentenceKind::Statement));
+    }
+    found
+}
+
+impl Contour {
+    pub fn flat() -> Self {
+        Contour { strength: 0.0, ..Contour::default() }
+    }
+
+    // (pitch scale, volume scale) for each character
+    pub fn shapes(&self, chars: &[char]) -> Vec<(f32, f32)> {
+        let mut shapes = vec![(1.0, 1.0); chars.len()];
+        for (range, kind) in sentences(chars) {
+            let letters: Vec<usize> = range.filter(|&i| chars[i].is_alphanumeric()).collect();
+            let n = letters.len();
+            for (pos, &i) in letters.iter().enumerate() {
+                let (pitch, volume) = match kind {
+                    SentenceKind::Statement => {
+                        let along = if n > 1 {
+                            pos as f32 / (n - 1) as f32
+                        } else {
+                            0.0
+                        };
+                        (1.0 - self.statement_fall * along, 1.0)
+                    }
+                    SentenceKind::Question => {
+                        let tail = ::std::cmp::min(self.question_tail, n);
+                        let into = (pos + tail + 1).saturating_sub(n);
+                        let along = if tail > 0 {
+                            into as f32 / tail as f32
+                        } else {
+                            0.0
+                        };
+                        (1.0 + self.question_rise * along, 1.0)
+                    }
+                    SentenceKind::Exclamation => {
+                        (1.0 + self.exclaim_pitch, 1.0 + self.exclaim_volume)
+                    }
+                };
+                shapes[i] = (1.0 + (pitch - 1.0) * self.strength,
+                             1.0 + (volume - 1.0) * self.strength);
+            }
+        }
+        shapes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sentences, Contour};
+    use super::SentenceKind::*;
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    fn near(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn splits_sentences() {
+        assert_eq!(sentences(&chars("Hi. You?")), vec![(0..3, Statement), (3..8, Question)]);
+        assert_eq!(sentences(&chars("Wait?! No!! ok")),
+                   vec![(0..6, Question), (6..11, Exclamation), (11..14, Statement)]);
+        // a number's point doesn't end one
+        assert_eq!(sentences(&chars("So… it's 3.14.")),
+                   vec![(0..3, Statement), (3..14, Statement)]);
+        // trailing space isn't a sentence of its own
+        assert_eq!(sentences(&chars("Hi.  ")), vec![(0..3, Statement)]);
+        assert_eq!(sentences(&chars("")), vec![]);
+    }
+
+    #[test]
+    fn questions_rise_and_statements_fall() {
+        let contour = Contour::default();
+        let question: Vec<f32> = contour.shapes(&chars("Is it?")).iter().map(|x| x.0).collect();
+        for (&got, &want) in question.iter().zip(&[1.075, 1.15, 1.0, 1.225, 1.3, 1.0]) {
+            assert!(near(got, want), "{:?}", question);
+        }
+        let statement: Vec<f32> = contour.shapes(&chars("abc.")).iter().map(|x| x.0).collect();
+        for (&got, &want) in statement.iter().zip(&[1.0, 0.95, 0.9, 1.0]) {
+            assert!(near(got, want), "{:?}", statement);
+        }
+        // exclamations are higher and louder all through
+        assert!(contour.shapes(&chars("Hey!"))[..3]
+            .iter()
+            .all(|&(p, v)| near(p, 1.15) && near(v, 1.25)));
+    }
+
+    #[test]
+    fn each_sentence_has_its_own_shape() {
+        let shapes = Contour::default().shapes(&chars("Go. Why?"));
+        // "Go" falls, then "Why" starts over and rises
+        assert!(near(shapes[0].0, 1.0) && near(shapes[1].0, 0.9));
+        assert!(shapes[4].0 < shapes[5].0 && shapes[5].0 < shapes[6].0);
+        assert!(near(shapes[6].0, 1.3));
+        // half strength, half as much; none, flat
+        let half = Contour { strength: 0.5, ..Contour::default() };
+        assert!(near(half.shapes(&chars("Go. Why?"))[6].0, 1.15));
+        assert!(Contour::flat().shapes(&chars("Go! Why?")).iter().all(|&x| x == (1.0, 1.0)));
+    }
+}
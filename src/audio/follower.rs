@@ -0,0 +1,90 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use super::SAMPLE_RATE;
+
+// An envelope follower: how loud a signal is right now, rising quickly when
+// it gets louder and falling off slowly after.
+#[derive(Clone, Copy, Debug)]
+pub struct Follower {
+    attack: f32,
+    release: f32,
+    level: f32,
+}
+
+// The latest level from a Follower in a stream callback, readable from
+// anywhere.
+#[derive(Clone, Debug, Default)]
+pub struct Level(Arc<AtomicU32>);
+
+impl Follower {
+    pub fn new(attack_millis: f32, release_millis: f32) -> Self {
+        // per-sample smoothing, so the level gets ~63% of the way there in
+        // the given time
+        let coeff = |millis: f32| (-1000.0 / (millis.max(0.01) * SAMPLE_RATE as f32)).exp();
+        Follower {
+            attack: coeff(attack_millis),
+            release: coeff(release_millis),
+            level: 0.0,
+        }
+    }
+    pub fn next(&mut self, x: f32) -> f32 {
+        let x = x.abs();
+        let k = if x > self.level {
+            self.attack
+        } else {
+            self.release
+        };
+        self.level = x + k * (self.level - x);
+        self.level
+    }
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+}
+
+impl Default for Follower {
+    fn default() -> Self {
+        Follower::new(2.0, 40.0)
+    }
+}
+
+impl Level {
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+    pub fn set(&self, x: f32) {
+        self.0.store(x.to_bits(), Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Follower, Level};
+
+    #[test]
+    fn levels_are_shared() {
+        let level = Level::default();
+        assert_eq!(level.get(), 0.0);
+        let other = level.clone();
+        other.set(0.25);
+        assert_eq!(level.get(), 0.25);
+        other.set(-1.5);
+        assert_eq!(level.get(), -1.5);
+    }
+
+    #[test]
+    fn rises_fast_and_falls_slow() {
+        let mut follower = Follower::default();
+        // the 2ms attack is about 88 samples
+        for _ in 0..1000 {
+            follower.next(-1.0);
+        }
+        let loud = follower.level();
+        assert!(loud > 0.99 && loud <= 1.0);
+        for _ in 0..100 {
+            follower.next(0.0);
+        }
+        assert!(follower.level() > 0.9 * loud);
+    }
+}
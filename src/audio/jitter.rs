@@ -0,0 +1,95 @@
+// Small random wobbles in pitch and length, so a voice sounds less
+// mechanical.  The randomness is seeded from the speaker and the line, so the
+// same line always comes out the same.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Jitter {
+    // pitch moves by up to this fraction either way
+    pub pitch: f32,
+    // character length moves by up to this fraction either way
+    pub duration: f32,
+}
+
+impl Default for Jitter {
+    fn default() -> Self {
+        Jitter {
+            pitch: 0.08,
+            duration: 0.15,
+        }
+    }
+}
+
+impl Jitter {
+    // (pitch scale, duration scale) for the next character
+    pub fn next(&self, rng: &mut Rng) -> (f32, f32) {
+        (1.0 + rng.spread(self.pitch), 1.0 + rng.spread(self.duration))
+    }
+}
+
+// FNV-1a, which unlike std's DefaultHasher is fixed across Rust versions.
+pub fn seed(speaker: &str, text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in speaker.bytes().chain(::std::iter::once(0)).chain(text.bytes()) {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+// xorshift64*
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck on zero
+        Rng(if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed })
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+    // uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+    // uniform in [-amount, amount)
+    pub fn spread(&mut self, amount: f32) -> f32 {
+        (self.next_f32() * 2.0 - 1.0) * amount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{seed, Jitter, Rng};
+
+    #[test]
+    fn seed_is_fnv_1a() {
+        // changing this changes how every saved voice sounds
+        assert_eq!(seed("robot", "Hi"), 0x7f9b_96bc_a12d_7bc2);
+        assert_ne!(seed("robot", "Hi"), seed("robotH", "i"));
+    }
+
+    #[test]
+    fn rng_repeats_from_a_seed() {
+        let mut a = Rng::new(seed("robot", "Hi"));
+        let mut b = Rng::new(seed("robot", "Hi"));
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert!(Rng::new(0).next_u64() != 0);
+    }
+
+    #[test]
+    fn next_stays_in_bounds() {
+        let jitter = Jitter::default();
+        let mut rng = Rng::new(1);
+        for _ in 0..10_000 {
+            let (pitch, duration) = jitter.next(&mut rng);
+            assert!(pitch >= 1.0 - jitter.pitch && pitch < 1.0 + jitter.pitch);
+            assert!(duration >= 1.0 - jitter.duration && duration < 1.0 + jitter.duration);
+        }
+    }
+}
@@ -1,8 +1,21 @@
 pub mod wave;
 pub mod tables;
+pub mod charmap;
+pub mod pacing;
+pub mod jitter;
+pub mod contour;
+pub mod animalese;
+pub mod voice;
+pub use self::voice::{Voice, SpeechEvent};
+pub mod relay;
+pub use self::relay::EventRelay;
+use self::relay::Heard;
+pub mod follower;
+pub use self::follower::{Follower, Level};
 
 use portaudio as pa;
 use std::{thread, time};
+use std::sync::Arc;
 
 const CHANNELS: i32 = 2;
 const SAMPLE_RATE: f64 = 44_100.0;
@@ -11,6 +24,10 @@ const FRAMES_PER_BUFFER: u32 = 64;
 pub struct Mixer {
     pub pa: pa::PortAudio,
     pub current_stream: Option<Stream>,
+    // how loud the stream is, for e.g. a talking portrait
+    pub level: Level,
+    // copied into each new stream to track `level`
+    pub follower: Follower,
 }
 
 impl Mixer {
@@ -19,6 +36,8 @@ impl Mixer {
         Ok(Mixer {
             pa: pa,
             current_stream: None,
+            level: Level::default(),
+            follower: Follower::default(),
         })
     }
     pub fn start(&mut self) -> Result<(), pa::Error> {
@@ -46,6 +65,7 @@ impl Mixer {
             r = x.close();
         }
         self.current_stream = None;
+        self.level.set(0.0);
         r
     }
     pub fn is_active(&self) -> Result<bool, pa::Error> {
@@ -74,6 +94,7 @@ impl Mixer {
         // This routine will be called by the PortAudio engine when audio is needed. It may called at
         // interrupt level on some machines so don't do anything that could mess up the system like
         // dynamic resource allocation or IO.
+        let (level, mut follower) = (self.level.clone(), self.follower);
         let callback = move |pa::OutputStreamCallbackArgs { buffer, frames, time, .. }| {
             let _ = time;
             let mut idx = 0;
@@ -83,10 +104,15 @@ impl Mixer {
                         buffer[idx] = x;
                         buffer[idx + 1] = x;
                         idx += 2;
+                        follower.next(x);
+                    }
+                    None => {
+                        level.set(0.0);
+                        return pa::Complete;
                     }
-                    None => return pa::Complete,
                 }
             }
+            level.set(follower.level());
             pa::Continue
         };
         let stream = self.pa.open_non_blocking_stream(settings, callback)?;
@@ -94,6 +120,59 @@ impl Mixer {
         Ok(())
     }
 
+    // Like new_stream, but also passes on each event once the stream reaches
+    // its sample.  Events must be sorted by sample.  The callback tags them
+    // with the DAC time their buffer will be heard at, and the EventRelay
+    // holds them back until then.
+    pub fn new_event_stream<T: 'static + Iterator<Item = f32>>(&mut self,
+                                                               mut data: T,
+                                                               events: Vec<SpeechEvent>)
+                                                               -> Result<EventRelay, pa::Error> {
+        let mut settings = try!(self.pa
+            .default_output_stream_settings(CHANNELS, SAMPLE_RATE, FRAMES_PER_BUFFER));
+        // we won't output out of range samples so don't bother clipping them.
+        settings.flags = pa::stream_flags::CLIP_OFF;
+
+        // the callback only needs where each event is, and marks them heard
+        // in place, so it doesn't allocate
+        let samples: Vec<usize> = events.iter().map(|e| e.sample).collect();
+        let heard = Arc::new(Heard::new(events.len()));
+        let relay = EventRelay::new(events, heard.clone());
+        let mut next = 0;
+        let mut played = 0;
+        let (level, mut follower) = (self.level.clone(), self.follower);
+        let callback = move |pa::OutputStreamCallbackArgs { buffer, frames, time, .. }| {
+            let mut idx = 0;
+            for frame in 0..frames {
+                while let Some(&sample) = samples.get(next) {
+                    if sample > played {
+                        break;
+                    }
+                    heard.push(time.buffer_dac + frame as f64 / SAMPLE_RATE);
+                    next += 1;
+                }
+                match data.next() {
+                    Some(x) => {
+                        buffer[idx] = x;
+                        buffer[idx + 1] = x;
+                        idx += 2;
+                        played += 1;
+                        follower.next(x);
+                    }
+                    None => {
+                        level.set(0.0);
+                        return pa::Complete;
+                    }
+                }
+            }
+            level.set(follower.level());
+            pa::Continue
+        };
+        let stream = self.pa.open_non_blocking_stream(settings, callback)?;
+        self.current_stream = Some(Stream(stream));
+        Ok(relay)
+    }
+
     pub fn new_cb_stream<F: 'static + FnMut(usize, f64, f64) -> Option<f32>>
         (&mut self,
          mut f: F)
@@ -106,6 +185,7 @@ impl Mixer {
         // This routine will be called by the PortAudio engine when audio is needed. It may called at
         // interrupt level on some machines so don't do anything that could mess up the system like
         // dynamic resource allocation or IO.
+        let (level, mut follower) = (self.level.clone(), self.follower);
         let callback = move |pa::OutputStreamCallbackArgs { buffer, frames, time, .. }| {
             let mut idx = 0;
             for frame in 0..frames {
@@ -114,10 +194,15 @@ impl Mixer {
                         buffer[idx] = x;
                         buffer[idx + 1] = x;
                         idx += 2;
+                        follower.next(x);
+                    }
+                    None => {
+                        level.set(0.0);
+                        return pa::Complete;
                     }
-                    None => return pa::Complete,
                 }
             }
+            level.set(follower.level());
             pa::Continue
         };
         let stream = self.pa.open_non_blocking_stream(settings, callback)?;
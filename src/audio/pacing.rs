@@ -0,0 +1,160 @@
+// Timing rules for a Voice: pauses after punctuation and speeding through
+// runs of the same letter ("HMMMMMM").
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Pacing {
+    // extra silence after , ; :
+    pub clause_millis: usize,
+    // extra silence after . ! ?
+    pub sentence_millis: usize,
+    // extra silence after ... or …
+    pub ellipsis_millis: usize,
+    // extra silence after — or --
+    pub dash_millis: usize,
+    // each repeat of a letter is this much shorter than the last...
+    pub repeat_speedup: f32,
+    // ...down to this fraction of a normal character
+    pub repeat_floor: f32,
+}
+
+// What the pacing rules do to one character: scale its length, then add a
+// pause after it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pace {
+    pub scale: f32,
+    pub pause_millis: usize,
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Pacing {
+            clause_millis: 150,
+            sentence_millis: 350,
+            ellipsis_millis: 500,
+            dash_millis: 250,
+            repeat_speedup: 0.8,
+            repeat_floor: 0.4,
+        }
+    }
+}
+
+impl Pacing {
+    // evenly spaced, like the old speech_wave
+    pub fn flat() -> Self {
+        Pacing {
+            clause_millis: 0,
+            sentence_millis: 0,
+            ellipsis_millis: 0,
+            dash_millis: 0,
+            repeat_speedup: 1.0,
+            repeat_floor: 1.0,
+        }
+    }
+
+    pub fn paces(&self, chars: &[char]) -> Vec<Pace> {
+        let mut paces = Vec::with_capacity(chars.len());
+        let mut repeats = 0;
+        for (i, &c) in chars.iter().enumerate() {
+            let prev = if i > 0 { Some(chars[i - 1]) } else { None };
+            let next = chars.get(i + 1).cloned();
+            if c.is_alphabetic() && prev == Some(c) {
+                repeats += 1;
+            } else {
+                repeats = 0;
+            }
+            let mut scale = 1.0;
+            for _ in 0..repeats {
+                scale *= self.repeat_speedup;
+            }
+            if scale < self.repeat_floor {
+                scale = self.repeat_floor;
+            }
+            paces.push(Pace {
+                scale,
+                pause_millis: self.pause_after(prev, c, next),
+            });
+        }
+        paces
+    }
+
+    fn pause_after(&self, prev: Option<char>, c: char, next: Option<char>) -> usize {
+        // "--" and "..." pause once, after the last character of the run
+        if next == Some(c) && (c == '.' || c == '-') {
+            return 0;
+        }
+        match c {
+            '—' => return self.dash_millis,
+            '-' if prev == Some('-') => return self.dash_millis,
+            '…' => return self.ellipsis_millis,
+            '.' if prev == Some('.') => return self.ellipsis_millis,
+            _ => {}
+        }
+        // "3.14" and "don't" shouldn't pause, so only pause at a word's end
+        let ends_word = match next {
+            None => true,
+            Some(n) => n.is_whitespace() || n == '"' || n == '\'' || n == ')',
+        };
+        if !ends_word {
+            return 0;
+        }
+        match c {
+            ',' | ';' | ':' => self.clause_millis,
+            '.' | '!' | '?' => self.sentence_millis,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pacing;
+
+    fn pauses(text: &str) -> Vec<usize> {
+        let chars: Vec<char> = text.chars().collect();
+        Pacing::default().paces(&chars).iter().map(|x| x.pause_millis).collect()
+    }
+
+    fn scales(text: &str) -> Vec<f32> {
+        let chars: Vec<char> = text.chars().collect();
+        Pacing::default().paces(&chars).iter().map(|x| x.scale).collect()
+    }
+
+    #[test]
+    fn pauses_at_the_end_of_words() {
+        assert_eq!(pauses("a, b"), vec![0, 150, 0, 0]);
+        assert_eq!(pauses("Hi."), vec![0, 0, 350]);
+        assert_eq!(pauses("(no.)"), vec![0, 0, 0, 350, 0]);
+        // not inside numbers or words
+        assert_eq!(pauses("3.14"), vec![0; 4]);
+        assert_eq!(pauses("1,000"), vec![0; 5]);
+        assert_eq!(pauses("e.g"), vec![0; 3]);
+    }
+
+    #[test]
+    fn runs_of_punctuation_pause_once() {
+        assert_eq!(pauses("so..."), vec![0, 0, 0, 0, 500]);
+        assert_eq!(pauses("so… ok"), vec![0, 0, 500, 0, 0, 0]);
+        assert_eq!(pauses("what?!"), vec![0, 0, 0, 0, 0, 350]);
+        assert_eq!(pauses("what?! no"), vec![0, 0, 0, 0, 0, 350, 0, 0, 0]);
+        assert_eq!(pauses("a--b"), vec![0, 0, 250, 0]);
+        assert_eq!(pauses("a—b"), vec![0, 250, 0]);
+        // a lone hyphen is part of the word
+        assert_eq!(pauses("x-ray"), vec![0; 5]);
+    }
+
+    #[test]
+    fn repeated_letters_speed_up() {
+        let hmm = scales("HMMMMMM");
+        assert_eq!(hmm[..3].to_vec(), vec![1.0, 1.0, 0.8]);
+        assert!((hmm[3] - 0.64).abs() < 1e-6);
+        // down to the floor
+        assert_eq!(hmm[6], 0.4);
+        // capitals are letters like any other, and each new one starts over
+        assert_eq!(scales("ALL-CAPS"), vec![1.0, 1.0, 0.8, 1.0, 1.0, 1.0, 1.0, 1.0]);
+        // only letters count
+        assert_eq!(scales("!!!"), vec![1.0; 3]);
+        assert_eq!(scales("111"), vec![1.0; 3]);
+        let chars: Vec<char> = "MMMM".chars().collect();
+        assert!(Pacing::flat().paces(&chars).iter().all(|x| x.scale == 1.0));
+    }
+}
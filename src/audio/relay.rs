@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use super::SpeechEvent;
+
+// The listening end of Mixer::new_event_stream.  Events are marked heard
+// early, as soon as their samples are handed to PortAudio; ready() only gives
+// them out once the stream clock says they're actually being heard.
+pub struct EventRelay {
+    // those not given out yet
+    events: VecDeque<SpeechEvent>,
+    heard: Arc<Heard>,
+    // how many have been given out
+    given: usize,
+}
+
+// When each event of a stream will be heard, filled in by the audio
+// callback.  It's all allocated up front and only written with atomics, as
+// the callback mustn't allocate or lock.  There's one writer, the callback,
+// and one reader, the relay.
+pub struct Heard {
+    // stream times as f64 bits, in event order
+    times: Vec<AtomicU64>,
+    // how many of `times` are filled in
+    count: AtomicUsize,
+}
+
+impl EventRelay {
+    // `events` as they'll be marked in `heard`
+    pub fn new(events: Vec<SpeechEvent>, heard: Arc<Heard>) -> Self {
+        EventRelay {
+            events: events.into_iter().collect(),
+            heard,
+            given: 0,
+        }
+    }
+
+    // events heard by stream time `now` (see Mixer::time), in order
+    pub fn ready(&mut self, now: f64) -> Vec<SpeechEvent> {
+        let mut ready = Vec::new();
+        while let Some(at) = self.heard.at(self.given) {
+            if at > now {
+                break;
+            }
+            ready.extend(self.events.pop_front());
+            self.given += 1;
+        }
+        ready
+    }
+
+    // the stream is gone and every event it reached has been given out
+    pub fn is_done(&self) -> bool {
+        Arc::strong_count(&self.heard) == 1 && self.heard.at(self.given).is_none()
+    }
+}
+
+impl Heard {
+    pub fn new(len: usize) -> Self {
+        Heard {
+            times: (0..len).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicUsize::new(0),
+        }
+    }
+    // From the callback: the next event is heard at stream time `at`.  Any
+    // past the number of events are dropped.
+    pub fn push(&self, at: f64) {
+        let n = self.count.load(Ordering::Relaxed);
+        if n < self.times.len() {
+            self.times[n].store(at.to_bits(), Ordering::Relaxed);
+            // publishes the time above along with the count
+            self.count.store(n + 1, Ordering::Release);
+        }
+    }
+    // when event `i` is heard, if the callback has got to it
+    pub fn at(&self, i: usize) -> Option<f64> {
+        if i < self.count.load(Ordering::Acquire) {
+            Some(f64::from_bits(self.times[i].load(Ordering::Relaxed)))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{EventRelay, Heard};
+    use audio::SpeechEvent;
+
+    fn event(index: usize) -> SpeechEvent {
+        SpeechEvent {
+            index,
+            text: index.to_string(),
+            sample: index * 100,
+        }
+    }
+
+    #[test]
+    fn gives_out_events_once_heard() {
+        let heard = Arc::new(Heard::new(3));
+        let mut relay = EventRelay::new(vec![event(0), event(1), event(2)], heard.clone());
+        assert!(relay.ready(10.0).is_empty());
+        heard.push(1.0);
+        heard.push(2.0);
+        assert!(relay.ready(0.5).is_empty());
+        assert_eq!(relay.ready(1.5), vec![event(0)]);
+        assert_eq!(relay.ready(5.0), vec![event(1)]);
+        heard.push(3.0);
+        // extra ones are dropped
+        heard.push(4.0);
+        assert!(!relay.is_done());
+        drop(heard);
+        assert!(!relay.is_done());
+        assert_eq!(relay.ready(f64::INFINITY), vec![event(2)]);
+        assert!(relay.is_done());
+    }
+
+    #[test]
+    fn done_when_the_stream_stops_early() {
+        let heard = Arc::new(Heard::new(2));
+        let mut relay = EventRelay::new(vec![event(0), event(1)], heard.clone());
+        heard.push(1.0);
+        drop(heard);
+        assert_eq!(relay.ready(f64::INFINITY), vec![event(0)]);
+        assert!(relay.is_done());
+    }
+}
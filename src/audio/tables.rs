@@ -16,6 +16,78 @@ pub fn sin_table(pitch: usize) -> Vec<f32> {
     v
 }
 
+pub fn square_table(pitch: usize) -> Vec<f32> {
+    if pitch == 0 {
+        return vec![0.0];
+    }
+    (0..pitch).map(|i| if i * 2 < pitch { 1.0 } else { -1.0 }).collect()
+}
+
+pub fn triangle_table(pitch: usize) -> Vec<f32> {
+    if pitch == 0 {
+        return vec![0.0];
+    }
+    (0..pitch)
+        .map(|i| {
+            let x = i as f32 / pitch as f32;
+            if x < 0.25 {
+                x * 4.0
+            } else if x < 0.75 {
+                2.0 - x * 4.0
+            } else {
+                x * 4.0 - 4.0
+            }
+        })
+        .collect()
+}
+
+pub fn saw_table(pitch: usize) -> Vec<f32> {
+    if pitch == 0 {
+        return vec![0.0];
+    }
+    // starts and ends on zero like the other tables, so loops join cleanly
+    (0..pitch)
+        .map(|i| {
+            let x = i as f32 / pitch as f32;
+            if x < 0.5 { x * 2.0 } else { x * 2.0 - 2.0 }
+        })
+        .collect()
+}
+
+// One period of a voiced tone: harmonics of the pitch, boosted near each
+// formant (hz, bandwidth hz) so it sounds like a vowel.
+pub fn formant_table(pitch: usize, formants: &[(f64, f64)]) -> Vec<f32> {
+    if pitch == 0 {
+        return vec![0.0];
+    }
+    // pitch = 45000 / hz
+    let base = 45_000.0 / pitch as f64;
+    let mut amps = Vec::new();
+    let mut k = 1;
+    while base * (k as f64) < 5_000.0 {
+        let hz = base * k as f64;
+        let mut boost = 1.0;
+        for &(f, bw) in formants {
+            boost += 4.0 / (1.0 + ((hz - f) / bw).powi(2));
+        }
+        amps.push(boost / k as f64);
+        k += 1;
+    }
+    let mut v: Vec<f64> = (0..pitch)
+        .map(|i| {
+            let x = (i as f64 / pitch as f64) * PI * 2.0;
+            amps.iter().enumerate().map(|(k, a)| a * (x * (k + 1) as f64).sin()).sum()
+        })
+        .collect();
+    let peak = v.iter().fold(0.0f64, |m, x| m.max(x.abs()));
+    if peak > 0.0 {
+        for x in v.iter_mut() {
+            *x /= peak;
+        }
+    }
+    v.into_iter().map(|x| x as f32).collect()
+}
+
 pub fn multi_sin_table(pitches: &[usize]) -> Vec<f32> {
     let pitches: Vec<usize> = pitches.iter().cloned().filter(|x| *x != 0).collect();
     if pitches.is_empty() {
@@ -0,0 +1,553 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::vec;
+
+use markup::Marked;
+use super::wave::{self, Waveform, LOOP_ADJUST};
+use super::tables::get_pitch;
+use super::charmap::{CharMap, CharClass, CharShape};
+use super::animalese::Syllables;
+use super::pacing::{Pace, Pacing};
+use super::jitter::{self, Jitter, Rng};
+use super::contour::Contour;
+
+// A speaking voice: turns a line of text into a string of beeps, one per
+// grapheme.  All pitches are chz (hertz*100) like the rest of audio::wave.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Voice {
+    // who is speaking; seeds the jitter
+    pub name: String,
+    pub base_chz: usize,
+    pub pitch: PitchPattern,
+    pub charmap: CharMap,
+    pub chars_per_sec: f32,
+    pub pacing: Pacing,
+    pub jitter: Option<Jitter>,
+    pub contour: Contour,
+    // what {em} does in markup
+    pub emphasis: CharShape,
+    // a blip per syllable instead of a beep per letter
+    pub syllables: Option<Syllables>,
+    pub waveform: Waveform,
+    pub envelope: Envelope,
+    pub gaps: Gaps,
+    pub volume: f32,
+}
+
+// How the pitch moves from one character to the next, as multipliers of
+// Voice::base_chz.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PitchPattern {
+    // character i gets pattern[i % len]
+    Cycle(Vec<f32>),
+    // each character maps to a fixed point between low and high, so the same
+    // letter always sounds the same
+    Range(f32, f32),
+}
+
+// Linear fade in/out on each beep, to keep the joins from clicking.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Envelope {
+    pub attack_millis: usize,
+    pub release_millis: usize,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Gaps {
+    // fraction of each character's time that is voiced, the rest is silence
+    pub voiced: f32,
+    // whitespace is silent for this many character lengths
+    pub whitespace: f32,
+    // silence smoothed onto each end of the line
+    pub bookend_millis: usize,
+}
+
+// the range of pitches beeps are kept to, 20hz to 20khz
+const MIN_CHZ: usize = 2_000;
+const MAX_CHZ: usize = 2_000_000;
+
+// One planned sound: `lead_millis` of silence, `millis` of tone at `chz`,
+// then `gap_millis` of silence.  A chz of 0 is all silence.
+#[derive(Clone, Debug)]
+pub struct Beep {
+    // where the grapheme starts in Marked::chars
+    pub index: usize,
+    pub text: String,
+    pub chz: usize,
+    pub lead_millis: usize,
+    pub millis: usize,
+    pub gap_millis: usize,
+    pub volume: f32,
+    pub waveform: Waveform,
+    pub envelope: Envelope,
+    // (hz, bandwidth hz) peaks replacing the waveform, see audio::animalese
+    pub formants: Option<[(f64, f64); 2]>,
+}
+
+// A grapheme being spoken: its tone starts `sample` samples into the line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpeechEvent {
+    pub index: usize,
+    pub text: String,
+    pub sample: usize,
+}
+
+impl Default for Voice {
+    // The old trials::speech_wave beeper's pitches and speed, with the
+    // default letter shapes, punctuation pauses and sentence contour.  Use
+    // CharMap::flat(), Pacing::flat() and Contour::flat() for the old sound.
+    fn default() -> Self {
+        Voice {
+            name: String::new(),
+            base_chz: 30_000,
+            pitch: PitchPattern::Cycle(vec![1.2, 1.0, 1.5, 0.75]),
+            charmap: CharMap::default(),
+            chars_per_sec: 10.0,
+            pacing: Pacing::default(),
+            jitter: None,
+            contour: Contour::default(),
+            emphasis: CharShape {
+                pitch: 1.1,
+                duration: 1.2,
+                volume: 1.25,
+            },
+            syllables: None,
+            waveform: Waveform::Sine,
+            envelope: Envelope::default(),
+            gaps: Gaps::default(),
+            // leaves room for exclamations
+            volume: 0.8,
+        }
+    }
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Envelope {
+            attack_millis: 5,
+            release_millis: 5,
+        }
+    }
+}
+
+impl Default for Gaps {
+    fn default() -> Self {
+        Gaps {
+            voiced: 1.0,
+            whitespace: 1.0,
+            bookend_millis: 25,
+        }
+    }
+}
+
+impl PitchPattern {
+    pub fn scale(&self, index: usize, c: char) -> f32 {
+        match *self {
+            PitchPattern::Cycle(ref v) => {
+                if v.is_empty() {
+                    1.0
+                } else {
+                    v[index % v.len()]
+                }
+            }
+            PitchPattern::Range(low, high) => {
+                let step = (c as u32 % 8) as f32 / 7.0;
+                low + (high - low) * step
+            }
+        }
+    }
+}
+
+impl Envelope {
+    pub fn apply(&self, samples: &mut [f32]) {
+        let n = samples.len();
+        let attack = ::std::cmp::min(self.attack_millis * LOOP_ADJUST, n / 2);
+        let release = ::std::cmp::min(self.release_millis * LOOP_ADJUST, n / 2);
+        for (i, x) in samples[..attack].iter_mut().enumerate() {
+            *x *= i as f32 / attack as f32;
+        }
+        for i in 0..release {
+            samples[n - 1 - i] *= i as f32 / release as f32;
+        }
+    }
+}
+
+impl Beep {
+    pub fn total_millis(&self) -> usize {
+        self.lead_millis + self.millis + self.gap_millis
+    }
+    // (lead, tone, gap) lengths in samples, matching render()
+    pub fn sample_lens(&self) -> (usize, usize, usize) {
+        let tone = if self.chz == 0 {
+            self.millis * LOOP_ADJUST
+        } else {
+            // the wave makers always give at least one whole period
+            let pitch = get_pitch(self.chz);
+            ::std::cmp::max((self.millis * LOOP_ADJUST) / pitch, 1) * pitch
+        };
+        (self.lead_millis * LOOP_ADJUST, tone, self.gap_millis * LOOP_ADJUST)
+    }
+    pub fn render(&self) -> Vec<f32> {
+        let mut tone: Vec<f32> = if self.chz == 0 {
+            wave::make_silence(self.millis).collect()
+        } else if let Some(ref formants) = self.formants {
+            wave::make_formant_wave(formants, self.chz, self.millis).collect()
+        } else {
+            wave::make_shaped_wave(self.waveform, self.chz, self.millis).collect()
+        };
+        self.envelope.apply(&mut tone);
+        let volume = self.volume;
+        let mut v: Vec<f32> = wave::make_silence(self.lead_millis).collect();
+        v.extend(tone.into_iter().map(|x| x * volume));
+        v.extend(wave::make_silence(self.gap_millis));
+        v
+    }
+}
+
+impl Voice {
+    // quick, chirpy, one blip per syllable, punctuation silent
+    pub fn animalese() -> Self {
+        Voice {
+            base_chz: 40_000,
+            pitch: PitchPattern::Range(0.9, 1.2),
+            charmap: CharMap {
+                rules: vec![(CharClass::Punctuation, CharShape::pitch(0.0)),
+                            (CharClass::Digit, CharShape::pitch(0.0))],
+                uppercase_pitch: 0.1,
+            },
+            chars_per_sec: 18.0,
+            syllables: Some(Syllables::default()),
+            ..Voice::default()
+        }
+    }
+
+    pub fn char_millis(&self) -> usize {
+        if self.chars_per_sec <= 0.0 {
+            return 0;
+        }
+        (1000.0 / self.chars_per_sec) as usize
+    }
+
+    pub fn jitter_seed(&self, text: &str) -> u64 {
+        jitter::seed(&self.name, text)
+    }
+
+    pub fn beeps(&self, text: &str) -> Vec<Beep> {
+        self.beeps_marked(&Marked::plain(text), &HashMap::new())
+    }
+
+    // Beeps for marked-up text.  {voice NAME} runs are spoken by voices[NAME]
+    // when it's there, and by self otherwise.  Each run is paced and
+    // intoned as part of the whole text, so a sentence that another voice
+    // cuts into still ends like one.
+    pub fn beeps_marked(&self, marked: &Marked, voices: &HashMap<String, Voice>) -> Vec<Beep> {
+        let clusters = marked.graphemes();
+        let chars: Vec<char> = clusters.iter().map(|r| marked.chars[r.start]).collect();
+        let len = clusters.len();
+        let mut beeps = Vec::with_capacity(len);
+        let mut start = 0;
+        while start < len {
+            let who = &marked.marks[clusters[start].start].voice;
+            let end = (start..len)
+                .find(|&i| marked.marks[clusters[i].start].voice != *who)
+                .unwrap_or(len);
+            let voice = who.as_ref().and_then(|x| voices.get(x)).unwrap_or(self);
+            let paces = voice.pacing.paces(&chars);
+            let contours = voice.contour.shapes(&chars);
+            beeps.extend(voice.run_beeps(marked,
+                                         &clusters[start..end],
+                                         &paces[start..end],
+                                         &contours[start..end]));
+            start = end;
+        }
+        if let Some(last) = beeps.last_mut() {
+            last.gap_millis += marked.end_pause_millis;
+        }
+        beeps
+    }
+
+    // Each grapheme is shaped by its first char, so an "e" with a combining
+    // accent sounds like an "e" and an emoji sequence is a single beep.
+    // `paces` and `contours` go with `clusters`.
+    fn run_beeps(&self,
+                 marked: &Marked,
+                 clusters: &[Range<usize>],
+                 paces: &[Pace],
+                 contours: &[(f32, f32)])
+                 -> Vec<Beep> {
+        let char_millis = self.char_millis();
+        let chars: Vec<char> = clusters.iter().map(|r| marked.chars[r.start]).collect();
+        let text: String = clusters.iter()
+            .flat_map(|r| marked.chars[r.clone()].iter())
+            .cloned()
+            .collect();
+        let mut rng = Rng::new(self.jitter_seed(&text));
+        let mut beeps = Vec::with_capacity(chars.len());
+        for (i, ((&c, pace), (contour_pitch, contour_volume))) in
+            chars.iter().zip(paces).zip(contours).enumerate() {
+            let index = clusters[i].start;
+            let mark = &marked.marks[index];
+            let grapheme: String = marked.chars[clusters[i].clone()].iter().cloned().collect();
+            if c.is_whitespace() {
+                let millis = char_millis as f32 * self.gaps.whitespace / mark.speed;
+                beeps.push(Beep {
+                    index,
+                    text: grapheme,
+                    chz: 0,
+                    lead_millis: mark.pause_millis,
+                    millis: millis as usize,
+                    gap_millis: (pace.pause_millis as f32 / mark.speed) as usize,
+                    volume: 0.0,
+                    waveform: self.waveform,
+                    envelope: self.envelope,
+                    formants: None,
+                });
+                continue;
+            }
+            let mut shape = self.charmap.shape(c);
+            if mark.emphasis {
+                shape.pitch *= self.emphasis.pitch;
+                shape.duration *= self.emphasis.duration;
+                shape.volume *= self.emphasis.volume;
+            }
+            let (jitter_pitch, jitter_duration) = match self.jitter {
+                Some(ref j) => j.next(&mut rng),
+                None => (1.0, 1.0),
+            };
+            let millis = (char_millis as f32 * shape.duration * pace.scale * jitter_duration /
+                          mark.speed) as usize;
+            let voiced = (millis as f32 * self.gaps.voiced) as usize;
+            let scale = self.pitch.scale(index, c) * shape.pitch * jitter_pitch *
+                        contour_pitch * mark.pitch;
+            let volume = self.volume * shape.volume * contour_volume * mark.volume;
+            beeps.push(Beep {
+                index,
+                text: grapheme,
+                chz: audible(self.base_chz as f32 * scale),
+                lead_millis: mark.pause_millis,
+                millis: voiced,
+                gap_millis: millis.saturating_sub(voiced) +
+                            (pace.pause_millis as f32 / mark.speed) as usize,
+                volume: if volume > 1.0 { 1.0 } else { volume },
+                waveform: self.waveform,
+                envelope: self.envelope,
+                formants: None,
+            });
+        }
+        if let Some(ref syllables) = self.syllables {
+            syllables.apply(&chars, &mut beeps);
+        }
+        beeps
+    }
+
+    // each grapheme with how long to wait before showing it, to keep a
+    // typed-out line in step with speak()
+    pub fn reveal(&self, text: &str) -> Vec<(String, usize)> {
+        reveal(&self.beeps(text))
+    }
+
+    pub fn reveal_marked(&self,
+                         marked: &Marked,
+                         voices: &HashMap<String, Voice>)
+                         -> Vec<(String, usize)> {
+        reveal(&self.beeps_marked(marked, voices))
+    }
+
+    pub fn speak(&self, text: &str) -> vec::IntoIter<f32> {
+        self.render(self.beeps(text))
+    }
+
+    pub fn speak_marked(&self,
+                        marked: &Marked,
+                        voices: &HashMap<String, Voice>)
+                        -> vec::IntoIter<f32> {
+        self.render(self.beeps_marked(marked, voices))
+    }
+
+    // speak_marked, along with when each grapheme's tone starts
+    pub fn speak_events(&self,
+                        marked: &Marked,
+                        voices: &HashMap<String, Voice>)
+                        -> (vec::IntoIter<f32>, Vec<SpeechEvent>) {
+        let beeps = self.beeps_marked(marked, voices);
+        let mut sample = self.gaps.bookend_millis * LOOP_ADJUST;
+        let mut events = Vec::with_capacity(beeps.len());
+        for b in beeps.iter() {
+            let (lead, tone, gap) = b.sample_lens();
+            events.push(SpeechEvent {
+                index: b.index,
+                text: b.text.clone(),
+                sample: sample + lead,
+            });
+            sample += lead + tone + gap;
+        }
+        (self.render(beeps), events)
+    }
+
+    // Every sample, worked out now rather than as the Mixer pulls them, so
+    // the audio callback only copies.
+    fn render(&self, beeps: Vec<Beep>) -> vec::IntoIter<f32> {
+        let mut samples = Vec::new();
+        for b in beeps.iter() {
+            samples.extend(b.render());
+        }
+        let wv: Box<DoubleEndedIterator<Item = f32>> = Box::new(samples.into_iter());
+        let all: Vec<f32> = wave::bookend(wv, self.gaps.bookend_millis).collect();
+        all.into_iter()
+    }
+}
+
+// Rounds to a chz that can be heard and rendered, however far markup and
+// jitter pushed it.  0 stays silent.
+fn audible(chz: f32) -> usize {
+    if chz <= 0.0 {
+        return 0;
+    }
+    chz.clamp(MIN_CHZ as f32, MAX_CHZ as f32) as usize
+}
+
+// a grapheme shows when its tone starts
+fn reveal(beeps: &[Beep]) -> Vec<(String, usize)> {
+    let mut last = 0;
+    beeps.iter()
+        .map(|b| {
+            let wait = last + b.lead_millis;
+            last = b.millis + b.gap_millis;
+            (b.text.clone(), wait)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::Voice;
+    use audio::jitter::Jitter;
+    use markup::Marked;
+
+    fn jittery(name: &str) -> Voice {
+        Voice {
+            name: name.to_owned(),
+            jitter: Some(Jitter::default()),
+            ..Voice::default()
+        }
+    }
+
+    // (chz, millis) of each beep
+    fn sounds(voice: &Voice, text: &str) -> Vec<(usize, usize)> {
+        voice.beeps(text).iter().map(|b| (b.chz, b.millis)).collect()
+    }
+
+    #[test]
+    fn jitter_repeats_for_the_same_line() {
+        let text = "The same line, twice.";
+        assert_eq!(sounds(&jittery("robot"), text), sounds(&jittery("robot"), text));
+    }
+
+    #[test]
+    fn jitter_differs_by_line_and_speaker() {
+        let text = "The same line, twice.";
+        let robot = sounds(&jittery("robot"), text);
+        assert!(robot != sounds(&jittery("robot"), "Another line, twice."));
+        assert!(robot != sounds(&jittery("android"), text));
+    }
+
+    #[test]
+    fn jitter_stays_in_bounds() {
+        let text = "Quite a long line, to get plenty of draws from the jitter.";
+        let jitter = Jitter::default();
+        let steady = Voice::default().beeps(text);
+        let wobbly = jittery("robot").beeps(text);
+        assert_eq!(steady.len(), wobbly.len());
+        let within = |x: usize, of: usize, by: f32| {
+            // both sides are truncated to whole chz and millis
+            let (x, of) = (x as f32, of as f32);
+            x >= of * (1.0 - by) - 1.0 && x <= of * (1.0 + by) + 1.0
+        };
+        for (a, b) in steady.iter().zip(wobbly.iter()) {
+            assert!(within(b.chz, a.chz, jitter.pitch), "{} vs {}", b.chz, a.chz);
+            assert!(within(b.millis, a.millis, jitter.duration),
+                    "{} vs {}",
+                    b.millis,
+                    a.millis);
+        }
+        assert!(steady.iter().zip(wobbly.iter()).any(|(a, b)| a.chz != b.chz));
+    }
+
+    fn with_other() -> HashMap<String, Voice> {
+        let mut voices = HashMap::new();
+        voices.insert("other".to_owned(), Voice { base_chz: 20_000, ..Voice::default() });
+        voices
+    }
+
+    #[test]
+    fn sentences_run_across_voices() {
+        let voice = Voice::default();
+        let whole = voice.beeps("Is this a question?");
+        let split = voice.beeps_marked(&Marked::parse("Is this {voice other}a{/voice} question?"),
+                                       &with_other());
+        assert_eq!(whole.len(), split.len());
+        // the start of the question isn't a statement trailing off
+        for i in 0..7 {
+            assert_eq!(whole[i].chz, split[i].chz, "{}", whole[i].text);
+        }
+        assert!(split[8].chz < whole[8].chz);
+        assert_eq!(split[18].chz, whole[18].chz);
+    }
+
+    #[test]
+    fn pacing_runs_across_voices() {
+        let voice = Voice::default();
+        let split = voice.beeps_marked(&Marked::parse("Pi is 3.{voice other}14{/voice}."),
+                                       &with_other());
+        let point = split.iter().find(|x| x.text == ".").unwrap();
+        // not the end of a sentence, as the other voice carries on the number
+        assert_eq!(point.gap_millis, 0);
+        assert_eq!(split.last().unwrap().gap_millis, voice.pacing.sentence_millis);
+    }
+
+    #[test]
+    fn marked_pitch_stays_audible() {
+        let voices = HashMap::new();
+        let high = Voice { base_chz: 1_000_000, ..Voice::default() };
+        let beeps = high.beeps_marked(&Marked::parse("{pitch 150}{pitch 150}Hi!"), &voices);
+        for b in beeps.iter() {
+            assert!(b.chz <= super::MAX_CHZ, "{}", b.chz);
+            // these divide by the wave's period, which mustn't be 0
+            b.sample_lens();
+            assert!(!b.render().is_empty());
+        }
+        let low = Voice::default().beeps_marked(&Marked::parse("{pitch 0.0001}a{pitch 0}b"),
+                                                &voices);
+        assert_eq!(low[0].chz, super::MIN_CHZ);
+        assert_eq!(low[1].chz, 0);
+    }
+
+    #[test]
+    fn speech_is_rendered_up_front() {
+        let voice = Voice::default();
+        let beeps = voice.beeps("Hi there.");
+        let tones: usize = beeps.iter().map(|b| b.render().len()).sum();
+        let wv = voice.speak("Hi there.");
+        // all there already, with nothing left to make
+        assert!(wv.len() > tones, "{} {}", wv.len(), tones);
+        let (wv, events) = voice.speak_events(&Marked::plain("Hi there."), &HashMap::new());
+        assert!(events.last().unwrap().sample < wv.len());
+    }
+
+    #[test]
+    fn speed_shortens_punctuation_pauses() {
+        let voice = Voice::default();
+        let slow = voice.beeps_marked(&Marked::parse("Hi."), &HashMap::new());
+        let fast = voice.beeps_marked(&Marked::parse("{speed 2}Hi.{/speed}"), &HashMap::new());
+        let pause = voice.pacing.sentence_millis;
+        assert!(slow[2].gap_millis >= pause);
+        assert!(fast[2].gap_millis < pause);
+        assert!(fast[2].gap_millis >= pause / 2);
+    }
+}
@@ -1,7 +1,8 @@
 use iters::{Smoother, Repeater, LimitRepeat};
 use std::vec::IntoIter;
 
-use super::tables::{get_pitch, sin_table, multi_sin_table, transition_table};
+use super::tables::{get_pitch, sin_table, square_table, triangle_table, saw_table,
+                    formant_table, multi_sin_table, transition_table};
 
 // num_values  = LOOP_ADJUST * millis
 pub const LOOP_ADJUST: usize = 45;
@@ -18,6 +19,54 @@ pub fn make_wave(chz: usize, milli_secs: usize) -> Repeater<IntoIter<f32>> {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Waveform {
+    Sine,
+    Square,
+    Triangle,
+    Saw,
+}
+
+impl Waveform {
+    pub fn table(&self, pitch: usize) -> Vec<f32> {
+        match *self {
+            Waveform::Sine => sin_table(pitch),
+            Waveform::Square => square_table(pitch),
+            Waveform::Triangle => triangle_table(pitch),
+            Waveform::Saw => saw_table(pitch),
+        }
+    }
+}
+
+// make_wave with a choice of waveform
+pub fn make_shaped_wave(form: Waveform,
+                        chz: usize,
+                        milli_secs: usize)
+                        -> Repeater<IntoIter<f32>> {
+    if chz == 0 {
+        Repeater::new(vec![0.0], LOOP_ADJUST * milli_secs)
+    } else {
+        let pitch = get_pitch(chz);
+        let loops = (milli_secs * LOOP_ADJUST) / pitch;
+        Repeater::new(form.table(pitch), loops)
+    }
+}
+
+// make_wave colored by formants, (hz, bandwidth hz) pairs
+pub fn make_formant_wave(formants: &[(f64, f64)],
+                         chz: usize,
+                         milli_secs: usize)
+                         -> Repeater<IntoIter<f32>> {
+    if chz == 0 {
+        Repeater::new(vec![0.0], LOOP_ADJUST * milli_secs)
+    } else {
+        let pitch = get_pitch(chz);
+        let loops = (milli_secs * LOOP_ADJUST) / pitch;
+        Repeater::new(formant_table(pitch, formants), loops)
+    }
+}
+
 pub fn make_silence(milli_secs: usize) -> LimitRepeat {
     let tics = milli_secs * LOOP_ADJUST;
     LimitRepeat {
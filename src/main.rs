@@ -1,11 +1,51 @@
 extern crate termion;
 extern crate portaudio;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
+extern crate serde_json;
+extern crate unicode_segmentation;
+extern crate unicode_width;
+extern crate signal_hook;
 
 pub mod term;
 pub mod audio;
 pub mod iters;
+pub mod markup;
+pub mod speaker;
+pub mod replay;
 pub mod trials;
 
+use std::env;
+use std::process;
+
+// `beeper replay LOG [SPEED]` plays back a saved log, anything else runs the
+// current trial
 fn main() {
-    trials::term_mock().unwrap();
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|x| x.as_str()) {
+        Some("replay") => {
+            let path = match args.get(1) {
+                Some(x) => x,
+                None => usage(),
+            };
+            let speed = match args.get(2).map(|x| x.parse::<f32>()) {
+                None => 1.0,
+                Some(Ok(x)) if x > 0.0 => x,
+                Some(_) => usage(),
+            };
+            // the terminal's back to normal by the time this returns
+            if let Err(e) = replay::replay_file(path, speed) {
+                eprintln!("beeper: {}", e);
+                process::exit(1);
+            }
+        }
+        _ => trials::term_mock().unwrap(),
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: beeper replay LOG [SPEED]");
+    process::exit(2);
 }
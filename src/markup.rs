@@ -0,0 +1,399 @@
+// Inline delivery markup for dialogue:
+//
+//     "Well{pause 400}... {speed 0.5}I suppose{/speed}"
+//
+// Tags are {pause MILLIS}, {speed X}..{/speed}, {pitch X}..{/pitch},
+// {volume X}..{/volume}, {voice NAME}..{/voice} and {em}..{/em}.  Speed, pitch
+// and volume are multipliers and nest, and speed also stretches the pauses
+// a voice makes at punctuation.  Speed, pitch and pauses are kept within the
+// limits below, however they nest.  {{ and }} are literal braces,
+// and anything in braces that isn't a known tag is kept as text.
+//
+// How the text looks is marked the same way: {b}, {i}, {u} and {dim} with
+// their closers, and {color C}..{/color} and {bg C}..{/bg} where C is a color
+// name, a 256 color index or #rrggbb.  {shake}, {wave}, {rainbow} and {fade}
+// with their closers animate it, see term::Effect:
+//
+//     "You found the {color yellow}{b}Brass Key{/b}{/color}!  {shake}Hands off!{/shake}"
+
+use std::cmp;
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use term::{Effect, Span, TextStyle};
+use term::screen::Color;
+
+// (lowest, highest) speed and pitch multipliers
+pub const SPEED_RANGE: (f32, f32) = (0.1, 10.0);
+pub const PITCH_RANGE: (f32, f32) = (0.0, 4.0);
+// the longest silence before a character
+pub const MAX_PAUSE_MILLIS: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Char(char),
+    Pause(usize),
+    Speed(f32),
+    EndSpeed,
+    Pitch(f32),
+    EndPitch,
+    Volume(f32),
+    EndVolume,
+    Voice(String),
+    EndVoice,
+    Emphasis,
+    EndEmphasis,
+    Bold,
+    EndBold,
+    Italic,
+    EndItalic,
+    Underline,
+    EndUnderline,
+    Dim,
+    EndDim,
+    Color(Color),
+    EndColor,
+    Background(Color),
+    EndBackground,
+    Effect(Effect),
+    EndEffect(Effect),
+}
+
+// How one displayed character should be delivered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mark {
+    pub speed: f32,
+    pub pitch: f32,
+    pub volume: f32,
+    pub emphasis: bool,
+    pub voice: Option<String>,
+    // silence before this character
+    pub pause_millis: usize,
+    pub style: TextStyle,
+}
+
+// The displayed text, with a Mark per character.
+#[derive(Clone, Debug, Default)]
+pub struct Marked {
+    pub chars: Vec<char>,
+    pub marks: Vec<Mark>,
+    // silence after the last character
+    pub end_pause_millis: usize,
+}
+
+impl Default for Mark {
+    fn default() -> Self {
+        Mark {
+            speed: 1.0,
+            pitch: 1.0,
+            volume: 1.0,
+            emphasis: false,
+            voice: None,
+            pause_millis: 0,
+            style: TextStyle::default(),
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            tokens.push(Token::Char(c));
+            rest = &rest[2..];
+            continue;
+        }
+        if c == '{' {
+            if let Some(end) = rest.find('}') {
+                if let Some(tok) = parse_tag(&rest[1..end]) {
+                    tokens.push(tok);
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            }
+        }
+        tokens.push(Token::Char(c));
+        rest = &rest[c.len_utf8()..];
+    }
+    tokens
+}
+
+fn parse_tag(tag: &str) -> Option<Token> {
+    let mut parts = tag.trim().splitn(2, ' ');
+    let name = parts.next().unwrap_or("");
+    let arg = parts.next().map(|x| x.trim());
+    let num = || arg.and_then(|x| x.parse::<f32>().ok()).filter(|x| *x >= 0.0);
+    Some(match (name, arg) {
+        ("pause", Some(_)) => Token::Pause(num()?.min(MAX_PAUSE_MILLIS as f32) as usize),
+        ("speed", Some(_)) => {
+            Token::Speed(num().filter(|x| *x > 0.0)?.clamp(SPEED_RANGE.0, SPEED_RANGE.1))
+        }
+        ("pitch", Some(_)) => Token::Pitch(num()?.clamp(PITCH_RANGE.0, PITCH_RANGE.1)),
+        ("volume", Some(_)) => Token::Volume(num()?),
+        ("voice", Some(x)) if !x.is_empty() => Token::Voice(x.to_owned()),
+        ("em", None) => Token::Emphasis,
+        ("b", None) => Token::Bold,
+        ("i", None) => Token::Italic,
+        ("u", None) => Token::Underline,
+        ("dim", None) => Token::Dim,
+        ("color", Some(x)) => Token::Color(Color::parse(x)?),
+        ("bg", Some(x)) => Token::Background(Color::parse(x)?),
+        ("/speed", None) => Token::EndSpeed,
+        ("/pitch", None) => Token::EndPitch,
+        ("/volume", None) => Token::EndVolume,
+        ("/voice", None) => Token::EndVoice,
+        ("/em", None) => Token::EndEmphasis,
+        ("/b", None) => Token::EndBold,
+        ("/i", None) => Token::EndItalic,
+        ("/u", None) => Token::EndUnderline,
+        ("/dim", None) => Token::EndDim,
+        ("/color", None) => Token::EndColor,
+        ("/bg", None) => Token::EndBackground,
+        (x, None) if x.starts_with('/') => Token::EndEffect(Effect::from_name(&x[1..])?),
+        (x, None) => Token::Effect(Effect::from_name(x)?),
+        _ => return None,
+    })
+}
+
+// Runs the tags over the text.  Unmatched closing tags are ignored.
+pub fn mark(tokens: &[Token]) -> Marked {
+    let mut marked = Marked::default();
+    let (mut speed, mut pitch, mut volume) = (vec![1.0f32], vec![1.0f32], vec![1.0f32]);
+    let mut voices: Vec<String> = Vec::new();
+    let mut emphasis = 0;
+    let mut pause = 0;
+    // how many of each are open
+    let (mut bold, mut italic, mut underline, mut dim) = (0, 0, 0, 0);
+    let (mut fg, mut bg): (Vec<Color>, Vec<Color>) = (Vec::new(), Vec::new());
+    let mut effects: Vec<Effect> = Vec::new();
+    for tok in tokens {
+        match *tok {
+            Token::Char(c) => {
+                marked.chars.push(c);
+                marked.marks.push(Mark {
+                    speed: *speed.last().unwrap(),
+                    pitch: *pitch.last().unwrap(),
+                    volume: *volume.last().unwrap(),
+                    emphasis: emphasis > 0,
+                    voice: voices.last().cloned(),
+                    pause_millis: pause,
+                    style: TextStyle {
+                        fg: fg.last().cloned(),
+                        bg: bg.last().cloned(),
+                        bold: bold > 0,
+                        italic: italic > 0,
+                        underline: underline > 0,
+                        dim: dim > 0,
+                        effects: effects.iter().cloned().collect(),
+                    },
+                });
+                pause = 0;
+            }
+            Token::Pause(x) => pause = cmp::min(pause.saturating_add(x), MAX_PAUSE_MILLIS),
+            Token::Speed(x) => {
+                let top = *speed.last().unwrap();
+                speed.push((top * x).clamp(SPEED_RANGE.0, SPEED_RANGE.1));
+            }
+            Token::Pitch(x) => {
+                let top = *pitch.last().unwrap();
+                pitch.push((top * x).clamp(PITCH_RANGE.0, PITCH_RANGE.1));
+            }
+            Token::Volume(x) => {
+                let top = *volume.last().unwrap();
+                volume.push(top * x);
+            }
+            Token::Voice(ref x) => voices.push(x.clone()),
+            Token::Emphasis => emphasis += 1,
+            Token::EndSpeed if speed.len() > 1 => {
+                speed.pop();
+            }
+            Token::EndPitch if pitch.len() > 1 => {
+                pitch.pop();
+            }
+            Token::EndVolume if volume.len() > 1 => {
+                volume.pop();
+            }
+            Token::EndVoice => {
+                voices.pop();
+            }
+            Token::EndEmphasis if emphasis > 0 => emphasis -= 1,
+            Token::Bold => bold += 1,
+            Token::EndBold if bold > 0 => bold -= 1,
+            Token::Italic => italic += 1,
+            Token::EndItalic if italic > 0 => italic -= 1,
+            Token::Underline => underline += 1,
+            Token::EndUnderline if underline > 0 => underline -= 1,
+            Token::Dim => dim += 1,
+            Token::EndDim if dim > 0 => dim -= 1,
+            Token::Color(c) => fg.push(c),
+            Token::EndColor => {
+                fg.pop();
+            }
+            Token::Background(c) => bg.push(c),
+            Token::EndBackground => {
+                bg.pop();
+            }
+            Token::Effect(x) => effects.push(x),
+            // the innermost one of its kind, so effects can overlap
+            Token::EndEffect(x) => {
+                if let Some(i) = effects.iter().rposition(|&e| e == x) {
+                    effects.remove(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    marked.end_pause_millis = pause;
+    marked
+}
+
+impl Marked {
+    pub fn parse(text: &str) -> Self {
+        mark(&parse(text))
+    }
+    // text with no markup at all
+    pub fn plain(text: &str) -> Self {
+        let chars: Vec<char> = text.chars().collect();
+        Marked {
+            marks: vec![Mark::default(); chars.len()],
+            chars,
+            end_pause_millis: 0,
+        }
+    }
+    // Everything `rate` times as fast, {pause} tags too, e.g. to replay a
+    // line sped up.
+    pub fn speed_up(&mut self, rate: f32) {
+        let shorten = |millis: usize| (millis as f32 / rate) as usize;
+        for mark in self.marks.iter_mut() {
+            mark.speed *= rate;
+            mark.pause_millis = shorten(mark.pause_millis);
+        }
+        self.end_pause_millis = shorten(self.end_pause_millis);
+    }
+    // what gets displayed, tags stripped
+    pub fn text(&self) -> String {
+        self.chars.iter().cloned().collect()
+    }
+    // Runs of styled text as Message spans, by byte offset into text().  Set
+    // them on a message before revealing the text into it.
+    pub fn spans(&self) -> Vec<Span> {
+        let mut spans: Vec<Span> = Vec::new();
+        let mut at = 0;
+        for (&c, mark) in self.chars.iter().zip(self.marks.iter()) {
+            let end = at + c.len_utf8();
+            let joined = match spans.last_mut() {
+                Some(ref mut last) if last.range.end == at && last.style == mark.style => {
+                    last.range.end = end;
+                    true
+                }
+                _ => false,
+            };
+            if !joined && !mark.style.is_plain() {
+                spans.push(Span {
+                    range: at..end,
+                    style: mark.style,
+                });
+            }
+            at = end;
+        }
+        spans
+    }
+    // the chars making up each grapheme cluster, e.g. a letter and its
+    // combining accents, or an emoji sequence
+    pub fn graphemes(&self) -> Vec<Range<usize>> {
+        let mut start = 0;
+        self.text()
+            .graphemes(true)
+            .map(|g| {
+                let end = start + g.chars().count();
+                let range = start..end;
+                start = end;
+                range
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Marked, Token, MAX_PAUSE_MILLIS};
+    use term::{Effect, Span, TextStyle};
+    use term::screen::Color;
+
+    fn chars(text: &str) -> Vec<Token> {
+        text.chars().map(Token::Char).collect()
+    }
+
+    #[test]
+    fn parses_tags_and_braces() {
+        let mut want = vec![Token::Pause(400), Token::Char('a'), Token::Speed(0.5)];
+        want.extend(chars("{b}"));
+        want.push(Token::EndSpeed);
+        assert_eq!(parse("{pause 400}a{ speed 0.5 }{{b}}{/speed}"), want);
+        assert_eq!(parse("{color #ff0000}"), vec![Token::Color(Color::Rgb(255, 0, 0))]);
+        assert_eq!(parse("{/shake}"), vec![Token::EndEffect(Effect::Shake)]);
+    }
+
+    #[test]
+    fn unknown_and_bad_tags_stay_as_text() {
+        for text in &["{hello}", "{speed fast}", "{speed 0}", "{pause -1}", "{b x}",
+                      "{color nope}", "{voice}", "{/fizz}", "{}"] {
+            assert_eq!(parse(text), chars(text), "{}", text);
+        }
+        // with no closing brace the rest is text, tags and all
+        assert_eq!(parse("a {b no end"), chars("a {b no end"));
+        let mut want = chars("{b x");
+        want.push(Token::Italic);
+        assert_eq!(parse("{b x{i}"), want);
+    }
+
+    #[test]
+    fn unclosed_tags_run_to_the_end() {
+        let marked = Marked::parse("a{b}b{/i}{/speed}c");
+        assert_eq!(marked.text(), "abc");
+        assert!(!marked.marks[0].style.bold);
+        assert!(marked.marks[1].style.bold && marked.marks[2].style.bold);
+        assert_eq!(marked.marks[2].speed, 1.0);
+        assert_eq!(marked.end_pause_millis, 0);
+    }
+
+    #[test]
+    fn span_ranges_are_bytes_of_the_stripped_text() {
+        let marked = Marked::parse("{pause 10}é {b}wörd{/b} {wave}世{/wave}!{pause 20}");
+        let text = marked.text();
+        assert_eq!(text, "é wörd 世!");
+        let bold = TextStyle { bold: true, ..TextStyle::default() };
+        let wave = TextStyle { effects: vec![Effect::Wave].into(), ..TextStyle::default() };
+        assert_eq!(marked.spans(),
+                   vec![Span { range: 3..8, style: bold }, Span { range: 9..12, style: wave }]);
+        assert_eq!(&text[3..8], "wörd");
+        assert_eq!(&text[9..12], "世");
+        assert_eq!(marked.marks[0].pause_millis, 10);
+        assert_eq!(marked.end_pause_millis, 20);
+    }
+
+    #[test]
+    fn numbers_are_kept_in_range() {
+        assert_eq!(parse("{pitch 150}"), vec![Token::Pitch(4.0)]);
+        assert_eq!(parse("{speed 0.001}"), vec![Token::Speed(0.1)]);
+        assert_eq!(parse("{speed inf}"), vec![Token::Speed(10.0)]);
+        assert_eq!(parse("{pause 1e20}"), vec![Token::Pause(MAX_PAUSE_MILLIS)]);
+        // and nested
+        let marked = Marked::parse("{pitch 3}{pitch 3}{speed 5}{speed 5}a\
+                                    {pause 9000}{pause 9000}b");
+        assert_eq!(marked.marks[0].pitch, 4.0);
+        assert_eq!(marked.marks[0].speed, 10.0);
+        assert_eq!(marked.marks[1].pause_millis, MAX_PAUSE_MILLIS);
+    }
+
+    #[test]
+    fn speed_up_shortens_pauses_too() {
+        let mut marked = Marked::parse("{speed 2}a{/speed}{pause 400}b{pause 100}");
+        marked.speed_up(4.0);
+        assert_eq!((marked.marks[0].speed, marked.marks[1].speed), (8.0, 4.0));
+        assert_eq!(marked.marks[1].pause_millis, 100);
+        assert_eq!(marked.end_pause_millis, 25);
+    }
+}
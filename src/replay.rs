@@ -0,0 +1,299 @@
+// Plays back a saved log (see term::record) the way it happened: each
+// message is typed out again in its speaker's voice, with the waits between
+// messages kept, sped up or slowed down by a factor.  Space pauses, Left and
+// Right jump to the previous or next message, PageUp and PageDown scroll and
+// Esc or q quits.
+
+use std::error::Error;
+use std::f64;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use termion::event::Key;
+
+use audio::{EventRelay, Mixer};
+use markup::Marked;
+use speaker::Registry;
+use term::{record, Backend, Input, Inputs, Record, Term};
+
+pub const MIN_SPEED: f32 = 0.5;
+pub const MAX_SPEED: f32 = 4.0;
+// the longest wait between messages, however long the player really sat
+// there
+const MAX_GAP_MILLIS: u64 = 3000;
+
+pub struct Replay<'a> {
+    records: Vec<Record>,
+    speakers: &'a Registry,
+    // 1.0 is as it happened
+    speed: f32,
+    // the message being played, or last played
+    pub current: usize,
+    pub paused: bool,
+    // the current message's speech, until it's all been heard
+    relay: Option<EventRelay>,
+    // millis since the current message started, stopped while paused
+    clock: f64,
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(records: Vec<Record>, speakers: &'a Registry, speed: f32) -> Self {
+        Replay {
+            records,
+            speakers,
+            speed: speed.clamp(MIN_SPEED, MAX_SPEED),
+            current: 0,
+            paused: false,
+            relay: None,
+            clock: 0.0,
+        }
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    // Plays from the first message until the user quits.  The last message
+    // stays up once it's done.
+    pub fn run<B: Backend>(&mut self,
+                           term: &mut Term<B>,
+                           mix: &mut Mixer,
+                           inputs: &Inputs)
+                           -> Result<(), Box<Error>> {
+        if self.records.is_empty() {
+            return Err(From::from("nothing to replay"));
+        }
+        self.jump(0, term, mix)?;
+        let mut last = Instant::now();
+        loop {
+            for input in inputs.poll() {
+                match input {
+                    Input::Key(Key::Esc) |
+                    Input::Key(Key::Char('q')) => {
+                        mix.close()?;
+                        return Ok(());
+                    }
+                    Input::Key(Key::Char(' ')) => self.pause(!self.paused, mix)?,
+                    Input::Key(Key::Left) => {
+                        let to = self.current.saturating_sub(1);
+                        self.jump(to, term, mix)?;
+                    }
+                    Input::Key(Key::Right) if self.current + 1 < self.records.len() => {
+                        let to = self.current + 1;
+                        self.jump(to, term, mix)?;
+                    }
+                    // the input line isn't used
+                    Input::Key(Key::PageUp) |
+                    Input::Key(Key::PageDown) |
+                    Input::Mouse(_) |
+                    Input::Resize(..) => {
+                        term.handle_input(input);
+                    }
+                    Input::Key(_) => {}
+                }
+            }
+            let elapsed = last.elapsed();
+            last = Instant::now();
+            if !self.paused {
+                self.clock += elapsed.as_secs() as f64 * 1000.0 +
+                              elapsed.subsec_nanos() as f64 / 1_000_000.0;
+                self.advance(term, mix)?;
+            }
+            term.status = self.status();
+            term.loudness = mix.level.get();
+            term.draw()?;
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    // e.g. "replay 3/12 1.5x paused"
+    pub fn status(&self) -> String {
+        let mut status = format!("replay {}/{} {}x",
+                                 self.current + 1,
+                                 self.records.len(),
+                                 self.speed);
+        if self.paused {
+            status.push_str(" paused");
+        }
+        status
+    }
+
+    pub fn pause(&mut self, paused: bool, mix: &mut Mixer) -> Result<(), Box<Error>> {
+        if paused != self.paused && self.relay.is_some() {
+            if paused {
+                mix.stop()?;
+            } else {
+                mix.start()?;
+            }
+        }
+        self.paused = paused;
+        Ok(())
+    }
+
+    // Starts message `to` over, with everything before it already in the
+    // log.
+    pub fn jump<B: Backend>(&mut self,
+                            to: usize,
+                            term: &mut Term<B>,
+                            mix: &mut Mixer)
+                            -> Result<(), Box<Error>> {
+        mix.close()?;
+        self.relay = None;
+        term.msg_buffer = None;
+        term.restore_log(self.records[..to].to_vec());
+        self.start(to, term, mix)
+    }
+
+    fn start<B: Backend>(&mut self,
+                         at: usize,
+                         term: &mut Term<B>,
+                         mix: &mut Mixer)
+                         -> Result<(), Box<Error>> {
+        let record = &self.records[at];
+        self.current = at;
+        self.clock = 0.0;
+        let mut msg = record.message();
+        msg.frozen = false;
+        let speaker = record.speaker.as_ref().and_then(|x| self.speakers.get(x));
+        match speaker {
+            Some(speaker) if !record.text.is_empty() => {
+                // spoken as it was marked up, if that was saved, at the
+                // replay's speed
+                let mut marked = match record.markup {
+                    Some(ref x) => Marked::parse(x),
+                    None => Marked::plain(&record.text),
+                };
+                marked.speed_up(self.speed);
+                let (wv, events) = speaker.voice.speak_events(&marked, &self.speakers.voices());
+                self.relay = Some(mix.new_event_stream(wv, events)?);
+                if !self.paused {
+                    mix.start()?;
+                }
+                msg.buffer.clear();
+                term.msg_buffer = Some(msg);
+            }
+            // the player typed theirs, so it shows up all at once
+            _ => {
+                term.msg_buffer = Some(msg);
+                term.msg_done();
+            }
+        }
+        Ok(())
+    }
+
+    // Reveals what's been heard, and moves on once the current message is
+    // done and its wait is over.
+    fn advance<B: Backend>(&mut self,
+                           term: &mut Term<B>,
+                           mix: &mut Mixer)
+                           -> Result<(), Box<Error>> {
+        if let Some(mut relay) = self.relay.take() {
+            let active = mix.is_active()?;
+            let now = if active { mix.time() } else { f64::INFINITY };
+            if let Some(ref mut msg) = term.msg_buffer {
+                for ev in relay.ready(now) {
+                    msg.buffer.push_str(&ev.text);
+                }
+            }
+            if active {
+                self.relay = Some(relay);
+                return Ok(());
+            }
+            mix.close()?;
+            term.msg_done();
+        }
+        let next = self.current + 1;
+        if next < self.records.len() && self.clock >= self.wait_millis(self.current) {
+            self.start(next, term, mix)?;
+        }
+        Ok(())
+    }
+
+    // how long after message `at` starts the next one does, at this speed
+    fn wait_millis(&self, at: usize) -> f64 {
+        let gap = match self.records.get(at + 1) {
+            Some(next) => next.time.saturating_sub(self.records[at].time),
+            None => 0,
+        };
+        ::std::cmp::min(gap, MAX_GAP_MILLIS) as f64 / self.speed as f64
+    }
+}
+
+// Replays the log at `path` in the terminal, with the speakers from
+// data/speakers.toml.
+pub fn replay_file(path: &str, speed: f32) -> Result<(), Box<Error>> {
+    let records = record::load(path)?;
+    let speakers = Registry::load("data/speakers.toml")?;
+    let mut mix = Mixer::new()?;
+    let mut term = Term::init()?;
+    let inputs = Inputs::spawn();
+    let result = Replay::new(records, &speakers, speed).run(&mut term, &mut mix, &inputs);
+    term.cleanup()?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use audio::Mixer;
+    use speaker::Registry;
+    use term::{Color, Record, Term, VirtualTerminal};
+    use super::{Replay, MAX_SPEED};
+
+    // the player's, so they show up at once with nothing to play
+    fn said(text: &str, time: u64) -> Record {
+        Record {
+            speaker: None,
+            name: "Player".to_owned(),
+            color: Color::Default,
+            text: text.to_owned(),
+            spans: Vec::new(),
+            markup: None,
+            time,
+        }
+    }
+
+    fn records() -> Vec<Record> {
+        vec![said("one", 0), said("two", 1000), said("three", 60_000)]
+    }
+
+    #[test]
+    fn status_and_waits() {
+        let speakers = Registry::new();
+        let mut replay = Replay::new(records(), &speakers, 2.0);
+        assert_eq!(replay.status(), "replay 1/3 2x");
+        replay.paused = true;
+        replay.current = 2;
+        assert_eq!(replay.status(), "replay 3/3 2x paused");
+        assert_eq!(replay.wait_millis(0), 500.0);
+        // long waits are cut short
+        assert_eq!(replay.wait_millis(1), 1500.0);
+        assert_eq!(replay.wait_millis(2), 0.0);
+        assert_eq!(Replay::new(records(), &speakers, 100.0).speed(), MAX_SPEED);
+    }
+
+    #[test]
+    fn jumps_and_advances() {
+        let speakers = Registry::new();
+        let mut replay = Replay::new(records(), &speakers, 1.0);
+        let mut term = Term::with_backend(VirtualTerminal::new(40, 10)).unwrap();
+        let mut mix = Mixer::new().unwrap();
+        replay.jump(2, &mut term, &mut mix).unwrap();
+        assert_eq!(replay.current, 2);
+        let shown: Vec<&str> = term.log.iter().map(|x| x.buffer.as_str()).collect();
+        assert_eq!(shown, vec!["one", "two", "three"]);
+        replay.jump(0, &mut term, &mut mix).unwrap();
+        assert_eq!(term.log.len(), 1);
+        // nothing moves on until the wait is over
+        replay.advance(&mut term, &mut mix).unwrap();
+        assert_eq!(replay.current, 0);
+        replay.clock = 999.0;
+        replay.advance(&mut term, &mut mix).unwrap();
+        assert_eq!(replay.current, 0);
+        replay.clock = 1000.0;
+        replay.advance(&mut term, &mut mix).unwrap();
+        assert_eq!(replay.current, 1);
+        assert_eq!(term.log.len(), 2);
+        term.draw().unwrap();
+        assert!(term.backend().rows().iter().any(|x| x.contains("<Player> two")));
+    }
+}
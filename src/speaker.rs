@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::SystemTime;
+
+use toml;
+
+use audio::Voice;
+use term::{Color, Message, Name, Portrait};
+use term::screen;
+
+// Someone who talks: how their name shows up, what they sound like and what
+// they look like.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Speaker {
+    pub id: String,
+    pub name: String,
+    // "#rrggbb", a color name or 256 color index, or 0-5 per channel of the
+    // color cube like [5, 5, 0]
+    #[serde(deserialize_with = "screen::deserialize_color",
+            serialize_with = "screen::serialize_color")]
+    pub color: Color,
+    #[serde(default)]
+    pub voice: Voice,
+    #[serde(default)]
+    pub portrait: Option<Portrait>,
+}
+
+// All the speakers an application knows, by id.  Loaded from a TOML file
+// like:
+//
+//     [[speaker]]
+//     id = "robot"
+//     name = "Testing Robot"
+//     color = "#ffd700"
+//
+//     [speaker.voice]
+//     base_chz = 25000
+//     chars_per_sec = 12.0
+//
+//     [speaker.portrait]
+//     closed = '''
+//      [o_o]
+//      [ - ]
+//     '''
+//     open = '''
+//      [o_o]
+//      [ O ]
+//     '''
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    speakers: HashMap<String, Speaker>,
+}
+
+#[derive(Deserialize)]
+struct RegistryFile {
+    #[serde(default)]
+    speaker: Vec<Speaker>,
+}
+
+impl Speaker {
+    // an empty message from this speaker, ready for Term::msg_buffer
+    pub fn message(&self) -> Message {
+        Message {
+            name: Name::Other(self.name.clone()),
+            speaker: Some(self.id.clone()),
+            markup: None,
+            time: SystemTime::now(),
+            color: self.color,
+            buffer: String::new(),
+            spans: Vec::new(),
+            reveals: Vec::new(),
+            frozen: false,
+        }
+    }
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry::default()
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<Error>> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        Registry::parse(&text)
+    }
+    pub fn parse(text: &str) -> Result<Self, Box<Error>> {
+        let file: RegistryFile = toml::from_str(text)?;
+        let mut reg = Registry::new();
+        for speaker in file.speaker {
+            if reg.speakers.contains_key(&speaker.id) {
+                return Err(From::from(format!("duplicate speaker id {:?}", speaker.id)));
+            }
+            reg.insert(speaker);
+        }
+        Ok(reg)
+    }
+    pub fn insert(&mut self, mut speaker: Speaker) {
+        // voices seed their jitter by name, so two speakers sharing a voice
+        // still sound different
+        if speaker.voice.name.is_empty() {
+            speaker.voice.name = speaker.id.clone();
+        }
+        self.speakers.insert(speaker.id.clone(), speaker);
+    }
+    pub fn get(&self, id: &str) -> Option<&Speaker> {
+        self.speakers.get(id)
+    }
+    pub fn ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = self.speakers.keys().map(|x| x.as_str()).collect();
+        ids.sort();
+        ids
+    }
+    // every voice by speaker id, for {voice ID} markup
+    pub fn voices(&self) -> HashMap<String, Voice> {
+        self.speakers.iter().map(|(id, s)| (id.clone(), s.voice.clone())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Registry;
+    use term::Color;
+
+    #[test]
+    fn parses_speakers() {
+        let reg = Registry::parse(r##"
+            [[speaker]]
+            id = "robot"
+            name = "Testing Robot"
+            color = "#ffd700"
+
+            [speaker.voice]
+            base_chz = 25000
+            chars_per_sec = 12.0
+
+            [[speaker]]
+            id = "cat"
+            name = "Cat"
+            color = [5, 0, 5]
+        "##)
+            .unwrap();
+        assert_eq!(reg.ids(), vec!["cat", "robot"]);
+        let robot = reg.get("robot").unwrap();
+        assert_eq!(robot.name, "Testing Robot");
+        assert_eq!(robot.color, Color::Rgb(0xff, 0xd7, 0));
+        assert_eq!((robot.voice.base_chz, robot.voice.chars_per_sec), (25_000, 12.0));
+        // the rest of the voice is as default
+        assert_eq!(robot.voice.volume, ::audio::Voice::default().volume);
+        assert!(robot.portrait.is_none());
+        assert_eq!(reg.voices().len(), 2);
+        assert!(Registry::parse("").unwrap().ids().is_empty());
+    }
+
+    #[test]
+    fn missing_voices_are_default_and_named() {
+        let reg = Registry::parse(r#"
+            [[speaker]]
+            id = "cat"
+            name = "Cat"
+            color = "red"
+
+            [[speaker]]
+            id = "dog"
+            name = "Dog"
+            color = "blue"
+
+            [speaker.voice]
+            name = "cat"
+        "#)
+            .unwrap();
+        let cat = &reg.get("cat").unwrap().voice;
+        assert_eq!(cat.base_chz, ::audio::Voice::default().base_chz);
+        // seeded by the speaker's id, unless it says otherwise
+        assert_eq!(cat.name, "cat");
+        assert_eq!(reg.get("dog").unwrap().voice.name, "cat");
+        assert_eq!(reg.voices()["dog"].name, "cat");
+    }
+
+    #[test]
+    fn bad_registries_are_errors() {
+        let err = Registry::parse(r#"
+            [[speaker]]
+            id = "cat"
+            name = "Cat"
+            color = "red"
+
+            [[speaker]]
+            id = "cat"
+            name = "Other Cat"
+            color = "blue"
+        "#)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "duplicate speaker id \"cat\"");
+        // no color, and a color that isn't one
+        assert!(Registry::parse("[[speaker]]\nid = \"a\"\nname = \"A\"").is_err());
+        assert!(Registry::parse("[[speaker]]\nid = \"a\"\nname = \"A\"\ncolor = \"x\"").is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use std::io::{self, Stdout, Write};
+use std::str;
+
+use termion;
+use termion::input::MouseTerminal;
+use termion::raw::RawTerminal;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::screen::{Screen, Style};
+
+// Where Term draws to: somewhere to write ANSI output that knows its size.
+pub trait Backend: Write {
+    // (columns, rows)
+    fn size(&self) -> io::Result<(u16, u16)>;
+}
+
+// the real terminal, in raw mode with mouse reporting
+pub type RawBackend = MouseTerminal<RawTerminal<Stdout>>;
+
+impl Backend for RawBackend {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        termion::terminal_size()
+    }
+}
+
+// A terminal in memory.  It follows the cursor movement, clearing and SGR
+// sequences Term writes, so what's "on screen" can be read back, e.g. in
+// tests or to embed Term somewhere else.
+pub struct VirtualTerminal {
+    pub screen: Screen,
+    // 1-based, like cursor::Goto
+    pub cursor: (u16, u16),
+    style: Style,
+    // a sequence or UTF-8 character split across writes
+    pending: Vec<u8>,
+}
+
+impl VirtualTerminal {
+    pub fn new(width: u16, height: u16) -> Self {
+        let mut screen = Screen::new(width, height);
+        screen.clear();
+        VirtualTerminal {
+            screen,
+            cursor: (1, 1),
+            style: Style::default(),
+            pending: Vec::new(),
+        }
+    }
+
+    // Changes size, like a resized window.  Everything on it is lost.
+    pub fn resize(&mut self, width: u16, height: u16) {
+        *self = VirtualTerminal::new(width, height);
+    }
+
+    // each row's text, top to bottom
+    pub fn rows(&self) -> Vec<String> {
+        (1..self.screen.size().1 + 1).map(|y| self.screen.row_text(y)).collect()
+    }
+
+    // Takes one escape sequence, or a run of text, off the front of `input`
+    // and acts on it.  Returns how many bytes were used, or None if `input`
+    // stops partway through.
+    fn feed(&mut self, input: &str) -> Option<usize> {
+        if input.starts_with('\x1b') {
+            return self.escape(input);
+        }
+        let end = input.find(['\x1b', '\n', '\r']).unwrap_or(input.len());
+        if end == 0 {
+            // raw mode: \n only moves down, \r only goes back
+            if input.starts_with('\n') {
+                self.cursor.1 = ::std::cmp::min(self.cursor.1 + 1, self.screen.size().1);
+            } else {
+                self.cursor.0 = 1;
+            }
+            return Some(1);
+        }
+        for g in input[..end].graphemes(true) {
+            let (x, y) = self.cursor;
+            let w = self.screen.put_grapheme(x, y, g, self.style);
+            self.cursor.0 = ::std::cmp::min(x + w as u16, self.screen.size().0 + 1);
+        }
+        Some(end)
+    }
+
+    fn escape(&mut self, input: &str) -> Option<usize> {
+        let mut chars = input.char_indices().skip(1);
+        match chars.next() {
+            Some((_, '[')) => {}
+            // some other escape we don't follow; skip the ESC and its letter
+            Some((i, c)) => return Some(i + c.len_utf8()),
+            None => return None,
+        }
+        let (end, fin) = chars.find(|&(_, c)| ('@'..='~').contains(&c))?;
+        let params = &input[2..end];
+        let nums: Vec<u16> = params.split(';').map(|x| x.parse().unwrap_or(0)).collect();
+        let (width, height) = self.screen.size();
+        let (x, y) = self.cursor;
+        match fin {
+            'm' => self.style.apply_sgr(params),
+            'H' | 'f' => {
+                let row = ::std::cmp::max(nums.first().cloned().unwrap_or(1), 1);
+                let col = ::std::cmp::max(nums.get(1).cloned().unwrap_or(1), 1);
+                self.cursor = (::std::cmp::min(col, width), ::std::cmp::min(row, height));
+            }
+            'J' => {
+                match nums[0] {
+                    // the cursor to the end of the screen
+                    0 => {
+                        self.screen.clear_row(x, y);
+                        for row in y + 1..height + 1 {
+                            self.screen.clear_row(1, row);
+                        }
+                    }
+                    _ => self.screen.clear(),
+                }
+            }
+            'K' => {
+                match nums[0] {
+                    0 => self.screen.clear_row(x, y),
+                    _ => self.screen.clear_row(1, y),
+                }
+            }
+            // modes (cursor shown, mouse, synchronized output...) don't
+            // change what's on screen
+            _ => {}
+        }
+        Some(end + fin.len_utf8())
+    }
+}
+
+impl Write for VirtualTerminal {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        let pending = ::std::mem::take(&mut self.pending);
+        // anything after invalid UTF-8 waits for the rest of its character
+        let valid = match str::from_utf8(&pending) {
+            Ok(x) => x.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        let text = str::from_utf8(&pending[..valid]).unwrap();
+        let mut used = 0;
+        while used < text.len() {
+            match self.feed(&text[used..]) {
+                Some(n) => used += n,
+                None => break,
+            }
+        }
+        self.pending = pending[used..].to_vec();
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Backend for VirtualTerminal {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        Ok(self.screen.size())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::{Duration, Instant, SystemTime};
+
+    use super::VirtualTerminal;
+    use term::{Color, Message, Name, Term};
+
+    fn write(vt: &mut VirtualTerminal, text: &[u8]) {
+        vt.write_all(text).unwrap();
+    }
+
+    fn trimmed(vt: &VirtualTerminal) -> Vec<String> {
+        vt.rows().iter().map(|x| x.trim_end().to_owned()).collect()
+    }
+
+    #[test]
+    fn term_draws_the_log_and_input_line() {
+        let mut term = Term::with_backend(VirtualTerminal::new(40, 12)).unwrap();
+        term.user_name = "Player".to_owned();
+        term.user_buffer = "typing".to_owned();
+        term.log.push(Message {
+            name: Name::Other("Robot".to_owned()),
+            speaker: None,
+            markup: None,
+            time: SystemTime::now(),
+            color: Color::Ansi(226),
+            buffer: "Hello there, this line is long enough to wrap".to_owned(),
+            spans: Vec::new(),
+            reveals: Vec::new(),
+            frozen: true,
+        });
+        term.draw().unwrap();
+        let rows = trimmed(term.backend());
+        assert_eq!(rows.len(), 12);
+        // the log is the 7 rows left over, with margins of 4 and 6
+        assert_eq!(rows[5], "    <Robot> Hello there, this line");
+        assert_eq!(rows[6], "            is long enough to wrap");
+        assert_eq!(rows[10], "<Player> typing");
+        let screen = &term.backend().screen;
+        assert_eq!(screen.cell(6, 6).unwrap().style.fg, Color::Ansi(226));
+        assert_eq!(screen.cell(5, 6).unwrap().style.fg, Color::Default);
+    }
+
+    #[test]
+    fn term_redraws_after_a_resize() {
+        let mut term = Term::with_backend(VirtualTerminal::new(40, 12)).unwrap();
+        term.user_name = "Player".to_owned();
+        term.draw().unwrap();
+        term.backend_mut().resize(20, 6);
+        term.resize();
+        term.draw().unwrap();
+        let rows = trimmed(term.backend());
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[4], "<Player>");
+    }
+
+    #[test]
+    fn follows_cursor_movement_and_sgr() {
+        let mut vt = VirtualTerminal::new(10, 3);
+        write(&mut vt, b"\x1b[2;3Hab\x1b[1;38;5;1mc\x1b[0md");
+        assert_eq!(trimmed(&vt), vec!["", "  abcd", ""]);
+        let c = vt.screen.cell(5, 2).unwrap();
+        assert!(c.style.bold);
+        assert_eq!(c.style.fg, Color::Ansi(1));
+        assert!(!vt.screen.cell(6, 2).unwrap().style.bold);
+        assert_eq!(vt.cursor, (7, 2));
+    }
+
+    #[test]
+    fn raw_newlines_only_move_down() {
+        let mut vt = VirtualTerminal::new(10, 3);
+        write(&mut vt, b"ab\ncd\r\nef");
+        assert_eq!(trimmed(&vt), vec!["ab", "  cd", "ef"]);
+    }
+
+    #[test]
+    fn sequences_split_across_writes() {
+        let mut vt = VirtualTerminal::new(10, 3);
+        write(&mut vt, b"\x1b[");
+        write(&mut vt, b"3;2Hx");
+        // é is two bytes, 世 is three
+        write(&mut vt, &[0xc3]);
+        write(&mut vt, &[0xa9, 0xe4, 0xb8]);
+        write(&mut vt, &[0x96, b'!']);
+        assert_eq!(trimmed(&vt)[2], " xé世!");
+        // the wide character's right half is empty
+        assert_eq!(vt.screen.cell(5, 3).unwrap().text, "");
+        assert_eq!(vt.screen.cell(6, 3).unwrap().text, "!");
+    }
+
+    #[test]
+    fn clears_lines_and_screen() {
+        let mut vt = VirtualTerminal::new(6, 3);
+        write(&mut vt, b"\x1b[1;1Habcdef\x1b[2;1Habcdef\x1b[3;1Habcdef");
+        write(&mut vt, b"\x1b[1;3H\x1b[K");
+        assert_eq!(trimmed(&vt), vec!["ab", "abcdef", "abcdef"]);
+        write(&mut vt, b"\x1b[2;4H\x1b[2K");
+        assert_eq!(trimmed(&vt), vec!["ab", "", "abcdef"]);
+        write(&mut vt, b"\x1b[3;5H\x1b[1;1H\x1b[3;3H\x1b[J");
+        assert_eq!(trimmed(&vt), vec!["ab", "", "ab"]);
+        write(&mut vt, b"\x1b[2J");
+        assert_eq!(trimmed(&vt), vec!["", "", ""]);
+    }
+
+    #[test]
+    fn modes_and_sync_are_ignored() {
+        let mut vt = VirtualTerminal::new(6, 1);
+        write(&mut vt, b"\x1b[?2026h\x1b[?25lhi\x1b[?2026l");
+        assert_eq!(trimmed(&vt), vec!["hi"]);
+    }
+
+    #[test]
+    fn wave_moves_within_the_live_pane() {
+        let mut term = Term::with_backend(VirtualTerminal::new(40, 12)).unwrap();
+        let mut msg = Message {
+            name: Name::Other("Robot".to_owned()),
+            speaker: None,
+            markup: None,
+            time: SystemTime::now(),
+            color: Color::Ansi(226),
+            buffer: String::new(),
+            spans: Vec::new(),
+            reveals: Vec::new(),
+            frozen: false,
+        };
+        msg.buffer = msg.set_markup("{wave}wibbly wobbly{/wave}").text();
+        term.msg_buffer = Some(msg);
+        term.status = "status".to_owned();
+        let mut rose = false;
+        for frame in 0..40 {
+            term.start = Instant::now() - Duration::from_millis(frame * 50 + 25);
+            term.draw().unwrap();
+            let rows = trimmed(term.backend());
+            // the live line is on row 9, with row 8 above it to move into
+            rose |= rows[7].contains(|c: char| c.is_alphabetic());
+            assert!(rows[6].is_empty(), "{:?}", rows);
+            assert_eq!(rows[9].trim(), "status");
+            assert!(rows[8].starts_with("<Robot> "), "{:?}", rows);
+        }
+        assert!(rose);
+    }
+}
@@ -0,0 +1,305 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use super::{Message, Theme};
+use super::screen::{self, Color, Glyph, Style};
+
+// what the page shows for Color::Default where the theme doesn't say, to
+// match xterm's ANSI_16 white on black
+const DEFAULT_FG: (u8, u8, u8) = (0xe5, 0xe5, 0xe5);
+const DEFAULT_BG: (u8, u8, u8) = (0x00, 0x00, 0x00);
+
+// How a transcript of the log is written out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    // just the text, for pasting anywhere
+    Plain,
+    // with SGR escapes, for `cat` or `less -R`
+    Ansi,
+    // a standalone page
+    Html,
+}
+
+impl Format {
+    // by extension: .html or .htm, .ans, and anything else is plain
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Format {
+        let ext = path.as_ref().extension().and_then(|x| x.to_str()).unwrap_or("");
+        match ext.to_lowercase().as_str() {
+            "html" | "htm" => Format::Html,
+            "ans" => Format::Ansi,
+            _ => Format::Plain,
+        }
+    }
+}
+
+// The log as Format, word wrapped the same as Term shows it if there's a
+// width.  Effects are at rest.
+pub fn export(log: &[Message], format: Format, width: Option<usize>, theme: &Theme) -> String {
+    match format {
+        Format::Plain => plain(log, width, theme),
+        Format::Ansi => ansi(log, width, theme),
+        Format::Html => html(log, width, theme, "Transcript"),
+    }
+}
+
+pub fn save<P: AsRef<Path>>(path: P,
+                            log: &[Message],
+                            width: Option<usize>,
+                            theme: &Theme)
+                            -> Result<(), Box<Error>> {
+    let format = Format::from_path(&path);
+    File::create(path)?.write_all(export(log, format, width, theme).as_bytes())?;
+    Ok(())
+}
+
+pub fn plain(log: &[Message], width: Option<usize>, theme: &Theme) -> String {
+    let mut out = String::new();
+    for msg in log {
+        for line in msg.rest_glyphs(width, theme) {
+            let text: String = line.iter().map(|g| g.text.as_str()).collect();
+            out.push_str(text.trim_end());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+pub fn ansi(log: &[Message], width: Option<usize>, theme: &Theme) -> String {
+    let mut out = String::new();
+    for msg in log {
+        for line in msg.rest_glyphs(width, theme) {
+            out.push_str(&screen::sgr_line(&line));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+// Each message is a <pre> with the speaker's id, if any, in data-speaker.
+// With no width the browser wraps lines to the page.
+pub fn html(log: &[Message], width: Option<usize>, theme: &Theme, title: &str) -> String {
+    // Color::Default, spelled out
+    let defaults = (theme.text.rgb().unwrap_or(DEFAULT_FG),
+                    theme.background.rgb().unwrap_or(DEFAULT_BG));
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{}</title>\n", escape(title)));
+    out.push_str("<style>\n");
+    out.push_str(&format!("body {{ background: {}; color: {}; }}\n",
+                          css_rgb(defaults.1),
+                          css_rgb(defaults.0)));
+    out.push_str(&format!("pre {{ margin: 0; font-family: monospace; white-space: {}; }}\n",
+                          if width.is_some() { "pre" } else { "pre-wrap" }));
+    out.push_str("</style>\n</head>\n<body>\n");
+    for msg in log {
+        match msg.speaker {
+            Some(ref id) => out.push_str(&format!("<pre data-speaker=\"{}\">", escape(id))),
+            None => out.push_str("<pre>"),
+        }
+        let lines: Vec<String> = msg.rest_glyphs(width, theme)
+            .iter()
+            .map(|x| html_line(x, defaults))
+            .collect();
+        out.push_str(&lines.join("\n"));
+        out.push_str("</pre>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+// a line as runs of same styled text, plain runs left bare
+fn html_line(glyphs: &[Glyph], defaults: ((u8, u8, u8), (u8, u8, u8))) -> String {
+    let mut line = String::new();
+    let mut i = 0;
+    while i < glyphs.len() {
+        let style = glyphs[i].style;
+        let mut text = String::new();
+        while i < glyphs.len() && glyphs[i].style == style {
+            text.push_str(&glyphs[i].text);
+            i += 1;
+        }
+        if style == Style::default() {
+            line.push_str(&escape(&text));
+        } else {
+            line.push_str(&format!("<span style=\"{}\">{}</span>",
+                                   css(&style, defaults),
+                                   escape(&text)));
+        }
+    }
+    line
+}
+
+// `defaults` are the (fg, bg) Color::Default stands for
+fn css(style: &Style, defaults: ((u8, u8, u8), (u8, u8, u8))) -> String {
+    let (mut fg, mut bg) = (style.fg, style.bg);
+    let mut rules = Vec::new();
+    if style.invert {
+        // the defaults swap too, so they have to be spelled out
+        rules.push(format!("color: {}", css_rgb(bg.rgb().unwrap_or(defaults.1))));
+        rules.push(format!("background-color: {}", css_rgb(fg.rgb().unwrap_or(defaults.0))));
+        fg = Color::Default;
+        bg = Color::Default;
+    }
+    if let Some(rgb) = fg.rgb() {
+        rules.push(format!("color: {}", css_rgb(rgb)));
+    }
+    if let Some(rgb) = bg.rgb() {
+        rules.push(format!("background-color: {}", css_rgb(rgb)));
+    }
+    if style.bold {
+        rules.push("font-weight: bold".to_owned());
+    }
+    if style.italic {
+        rules.push("font-style: italic".to_owned());
+    }
+    if style.underline {
+        rules.push("text-decoration: underline".to_owned());
+    }
+    if style.dim {
+        rules.push("opacity: 0.5".to_owned());
+    }
+    rules.join("; ")
+}
+
+fn css_rgb((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::{ansi, css, html, plain, Format};
+    use term::{Color, Message, Name, Span, TextStyle, Theme};
+    use term::screen::Style;
+
+    fn log() -> Vec<Message> {
+        let bold_blue = TextStyle {
+            fg: Some(Color::Rgb(0, 128, 255)),
+            bold: true,
+            ..TextStyle::default()
+        };
+        vec![Message {
+                 name: Name::Other("Ann".to_owned()),
+                 speaker: Some("a\"b".to_owned()),
+                 markup: None,
+                 time: SystemTime::now(),
+                 color: Color::Ansi(1),
+                 buffer: "x<y & \"z\"".to_owned(),
+                 spans: vec![Span { range: 0..1, style: bold_blue }],
+                 reveals: Vec::new(),
+                 frozen: true,
+             },
+             Message {
+                 name: Name::Other("Bo".to_owned()),
+                 speaker: None,
+                 markup: None,
+                 time: SystemTime::now(),
+                 color: Color::Default,
+                 buffer: "one two".to_owned(),
+                 spans: Vec::new(),
+                 reveals: Vec::new(),
+                 frozen: true,
+             }]
+    }
+
+    #[test]
+    fn formats_by_extension() {
+        assert_eq!(Format::from_path("log.HTML"), Format::Html);
+        assert_eq!(Format::from_path("log.ans"), Format::Ansi);
+        assert_eq!(Format::from_path("log.txt"), Format::Plain);
+        assert_eq!(Format::from_path("log"), Format::Plain);
+    }
+
+    #[test]
+    fn plain_text() {
+        let theme = Theme::dark();
+        assert_eq!(plain(&log(), None, &theme), "<Ann> x<y & \"z\"\n<Bo> one two\n");
+        // wrapped under the name where there's room for it
+        assert_eq!(plain(&log(), Some(12), &theme),
+                   "<Ann> x<y &\n      \"z\"\n<Bo> one two\n");
+        assert_eq!(plain(&log(), Some(11), &theme),
+                   "<Ann> x<y &\n\"z\"\n<Bo> one\n     two\n");
+    }
+
+    #[test]
+    fn ansi_text() {
+        // the first 16 colors by their own codes, and back to plain at
+        // the end of each line
+        assert_eq!(ansi(&log(), None, &Theme::dark()),
+                   "<\x1b[31mAnn\x1b[39m> \x1b[1;38;2;0;128;255mx\x1b[22;31m<y & \"z\"\x1b[39m\n\
+                    <Bo> one two\n");
+    }
+
+    #[test]
+    fn html_page() {
+        let page = html(&log(), Some(20), &Theme::dark(), "<Log> & \"more\"");
+        assert_eq!(page,
+                   concat!(r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>&lt;Log&gt; &amp; &quot;more&quot;</title>
+<style>
+body { background: #000000; color: #e5e5e5; }
+pre { margin: 0; font-family: monospace; white-space: pre; }
+</style>
+</head>
+<body>
+<pre data-speaker="a&quot;b">&lt;<span style="color: #cd0000">Ann</span>&gt; "#,
+                           r#"<span style="color: #0080ff; font-weight: bold">x</span>"#,
+                           r#"<span style="color: #cd0000">&lt;y &amp; &quot;z&quot;</span></pre>
+<pre>&lt;Bo&gt; one two</pre>
+</body>
+</html>
+"#));
+        // the browser wraps when there's no width, and the theme's own
+        // colors stand in for the defaults
+        let page = html(&log(), None, &Theme::light(), "Log");
+        assert!(page.contains("body { background: #ffffff; color: #000000; }"));
+        assert!(page.contains("white-space: pre-wrap;"));
+    }
+
+    #[test]
+    fn styles_as_css() {
+        let defaults = ((1, 2, 3), (4, 5, 6));
+        assert_eq!(css(&Style::default(), defaults), "");
+        let style = Style {
+            fg: Color::Ansi(9),
+            bg: Color::cube(5, 0, 0),
+            italic: true,
+            underline: true,
+            dim: true,
+            ..Style::default()
+        };
+        assert_eq!(css(&style, defaults),
+                   "color: #ff0000; background-color: #ff0000; font-style: italic; \
+                    text-decoration: underline; opacity: 0.5");
+        // inverted defaults are spelled out, swapped
+        let style = Style { invert: true, ..Style::default() };
+        assert_eq!(css(&style, defaults), "color: #040506; background-color: #010203");
+        let style = Style {
+            fg: Color::Rgb(0x12, 0x34, 0x56),
+            invert: true,
+            ..Style::default()
+        };
+        assert_eq!(css(&style, defaults), "color: #040506; background-color: #123456");
+    }
+}
@@ -0,0 +1,61 @@
+use std::io;
+use std::sync::mpsc;
+use std::thread;
+
+use signal_hook::consts::SIGWINCH;
+use signal_hook::iterator::Signals;
+use termion;
+use termion::event::{Event, Key, MouseEvent};
+use termion::input::TermRead;
+
+// Something the user did at the terminal.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Input {
+    Key(Key),
+    Mouse(MouseEvent),
+    // the terminal is now this many columns and rows
+    Resize(u16, u16),
+}
+
+// Reads stdin on its own thread so the draw loop never blocks on it, and
+// passes on SIGWINCH from another.
+pub struct Inputs {
+    chan: mpsc::Receiver<Input>,
+}
+
+impl Inputs {
+    pub fn spawn() -> Self {
+        let (send, rcv) = mpsc::channel();
+        // without the signal, resizes just go unnoticed
+        if let Ok(mut signals) = Signals::new([SIGWINCH]) {
+            let send = send.clone();
+            thread::spawn(move || for _ in signals.forever() {
+                let (w, h) = match termion::terminal_size() {
+                    Ok(x) => x,
+                    Err(_) => continue,
+                };
+                if send.send(Input::Resize(w, h)).is_err() {
+                    return;
+                }
+            });
+        }
+        thread::spawn(move || {
+            for event in io::stdin().events() {
+                let input = match event {
+                    Ok(Event::Key(k)) => Input::Key(k),
+                    Ok(Event::Mouse(m)) => Input::Mouse(m),
+                    Ok(_) => continue,
+                    Err(_) => return,
+                };
+                if send.send(input).is_err() {
+                    return;
+                }
+            }
+        });
+        Inputs { chan: rcv }
+    }
+    // everything that has come in since the last poll
+    pub fn poll(&self) -> Vec<Input> {
+        self.chan.try_iter().collect()
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+
+// The parts of Term's screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Pane {
+    Log,
+    Portrait,
+    // the message being spoken right now
+    Message,
+    Status,
+    Input,
+}
+
+// Columns and rows, 1-based like cursor::Goto.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+// How much of a stack or split a child gets.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Size {
+    Fixed(u16),
+    Percent(u16),
+    // a share, by weight, of whatever Fixed and Percent leave over
+    Fill(u16),
+}
+
+// How Term's screen is carved up into panes:
+//
+//     Layout::Split(vec![(Size::Fixed(20), Layout::Pane(Pane::Portrait)),
+//                        (Size::Fill(1), Layout::Stack(vec![...]))])
+//
+// Children that don't fit are squeezed down to nothing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Layout {
+    Pane(Pane),
+    // blank space
+    Empty,
+    // children top to bottom
+    Stack(Vec<(Size, Layout)>),
+    // children left to right
+    Split(Vec<(Size, Layout)>),
+    // blank columns and rows around a layout
+    Pad {
+        left: u16,
+        right: u16,
+        top: u16,
+        bottom: u16,
+        inner: Box<Layout>,
+    },
+}
+
+impl Rect {
+    pub fn bottom(&self) -> u16 {
+        self.y + self.height.saturating_sub(1)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+}
+
+impl Default for Layout {
+    // the log, then the live message, status bar and input.  Term puts its
+    // theme's margins around the log.  The live message is a line with a
+    // row above it for effects like {wave} to move into.
+    fn default() -> Self {
+        let pane = |pane, right| Layout::pad(0, right, 0, 0, Layout::Pane(pane));
+        Layout::Stack(vec![(Size::Fill(1), Layout::Pane(Pane::Log)),
+                           (Size::Fixed(2), pane(Pane::Message, 1)),
+                           (Size::Fixed(1), pane(Pane::Status, 0)),
+                           (Size::Fixed(1), pane(Pane::Input, 1)),
+                           (Size::Fixed(1), Layout::Empty)])
+    }
+}
+
+impl Layout {
+    pub fn pad(left: u16, right: u16, top: u16, bottom: u16, inner: Layout) -> Self {
+        Layout::Pad {
+            left,
+            right,
+            top,
+            bottom,
+            inner: Box::new(inner),
+        }
+    }
+
+    // the default layout with a portrait `width` columns wide down the left
+    pub fn with_portrait(width: u16) -> Self {
+        Layout::Split(vec![(Size::Fixed(width),
+                            Layout::pad(1, 0, 1, 1, Layout::Pane(Pane::Portrait))),
+                           (Size::Fill(1), Layout::default())])
+    }
+
+    // where each pane ends up within `area`
+    pub fn regions(&self, area: Rect) -> HashMap<Pane, Rect> {
+        let mut found = HashMap::new();
+        self.place(area, &mut found);
+        found
+    }
+
+    fn place(&self, area: Rect, found: &mut HashMap<Pane, Rect>) {
+        match *self {
+            Layout::Pane(pane) => {
+                found.insert(pane, area);
+            }
+            Layout::Empty => {}
+            Layout::Stack(ref children) => {
+                let sizes = divide(area.height, children);
+                let mut y = area.y;
+                for ((_, child), height) in children.iter().zip(sizes) {
+                    child.place(Rect { y, height, ..area }, found);
+                    y += height;
+                }
+            }
+            Layout::Split(ref children) => {
+                let sizes = divide(area.width, children);
+                let mut x = area.x;
+                for ((_, child), width) in children.iter().zip(sizes) {
+                    child.place(Rect { x, width, ..area }, found);
+                    x += width;
+                }
+            }
+            Layout::Pad { left, right, top, bottom, ref inner } => {
+                let width = area.width.saturating_sub(left).saturating_sub(right);
+                let height = area.height.saturating_sub(top).saturating_sub(bottom);
+                inner.place(Rect {
+                                x: area.x + ::std::cmp::min(left, area.width),
+                                y: area.y + ::std::cmp::min(top, area.height),
+                                width,
+                                height,
+                            },
+                            found);
+            }
+        }
+    }
+}
+
+// Shares `total` out between children: Fixed and Percent first, in order,
+// then the rest between the Fills.
+fn divide(total: u16, children: &[(Size, Layout)]) -> Vec<u16> {
+    let mut left = total;
+    let mut sizes: Vec<u16> = children.iter()
+        .map(|&(size, _)| {
+            let want = match size {
+                Size::Fixed(n) => n,
+                Size::Percent(p) => (total as u32 * p as u32 / 100) as u16,
+                Size::Fill(_) => 0,
+            };
+            let got = ::std::cmp::min(want, left);
+            left -= got;
+            got
+        })
+        .collect();
+    let weights: u32 = children.iter()
+        .map(|&(size, _)| match size {
+            Size::Fill(w) => w as u32,
+            _ => 0,
+        })
+        .sum();
+    if weights == 0 {
+        return sizes;
+    }
+    let spare = left as u32;
+    let mut seen = 0;
+    let mut given = 0;
+    for (i, &(size, _)) in children.iter().enumerate() {
+        if let Size::Fill(w) = size {
+            // by running total, so the rounding comes out even
+            seen += w as u32;
+            let upto = spare * seen / weights;
+            sizes[i] = (upto - given) as u16;
+            given = upto;
+        }
+    }
+    sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{divide, Layout, Pane, Rect, Size};
+
+    fn sized(sizes: &[Size]) -> Vec<(Size, Layout)> {
+        sizes.iter().map(|&x| (x, Layout::Empty)).collect()
+    }
+
+    #[test]
+    fn fixed_and_percent_come_first() {
+        let children = sized(&[Size::Fill(1), Size::Fixed(3), Size::Percent(50)]);
+        assert_eq!(divide(20, &children), vec![7, 3, 10]);
+        // rounded down
+        let children = sized(&[Size::Percent(33), Size::Fill(1)]);
+        assert_eq!(divide(10, &children), vec![3, 7]);
+    }
+
+    #[test]
+    fn fills_share_the_rest_evenly() {
+        let children = sized(&[Size::Fill(1), Size::Fill(1), Size::Fill(1)]);
+        assert_eq!(divide(10, &children), vec![3, 3, 4]);
+        let children = sized(&[Size::Fill(2), Size::Fixed(1), Size::Fill(1)]);
+        assert_eq!(divide(11, &children), vec![6, 1, 4]);
+        let total: u16 = divide(97, &sized(&[Size::Fill(3), Size::Fill(5), Size::Fill(7)]))
+            .iter()
+            .sum();
+        assert_eq!(total, 97);
+    }
+
+    #[test]
+    fn zero_sizes() {
+        let children = sized(&[Size::Fixed(2), Size::Fill(1), Size::Percent(50)]);
+        assert_eq!(divide(0, &children), vec![0, 0, 0]);
+        // too little room: earlier ones win, and Fills get nothing
+        assert_eq!(divide(3, &children), vec![2, 0, 1]);
+        let children = sized(&[Size::Fill(0), Size::Fixed(0)]);
+        assert_eq!(divide(5, &children), vec![0, 0]);
+        assert_eq!(divide(5, &[]), Vec::<u16>::new());
+    }
+
+    #[test]
+    fn regions_of_a_tiny_screen() {
+        let area = Rect { x: 1, y: 1, width: 4, height: 3 };
+        let regions = Layout::default().regions(area);
+        // the fixed rows take it all, from the top
+        assert_eq!(regions[&Pane::Log].height, 0);
+        assert_eq!(regions[&Pane::Message], Rect { x: 1, y: 1, width: 3, height: 2 });
+        assert_eq!(regions[&Pane::Status], Rect { x: 1, y: 3, width: 4, height: 1 });
+        assert_eq!(regions[&Pane::Input].height, 0);
+    }
+}
@@ -1,105 +1,638 @@
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::Range;
+use std::time::SystemTime;
 
-use termion::color;
+use markup::Marked;
+use termion::style;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::Theme;
+use super::screen::{self, Color, Glyph, Style};
 
 #[derive(Clone)]
-pub struct Message<'a> {
-    pub name: Name<'a>,
-    pub color: color::AnsiValue,
-    pub buffer: Vec<char>,
+pub struct Message {
+    pub name: Name,
+    // the id of the Speaker it's from, if any
+    pub speaker: Option<String>,
+    // what it was said in before the tags were taken out, if it was marked
+    // up, see set_markup
+    pub markup: Option<String>,
+    // when it was started
+    pub time: SystemTime,
+    pub color: Color,
+    pub buffer: String,
+    // styled parts of buffer; the rest is plain `color`
+    pub spans: Vec<Span>,
+    // (buffer length, frame) each time more of the buffer was drawn
+    pub reveals: Vec<(usize, u64)>,
+    // effects are drawn at rest, e.g. once the message is in the log
+    pub frozen: bool,
+}
+
+// Animated span effects, run off Term's frame clock.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    // jitters about, angrily
+    Shake,
+    // bobs up and down along the line
+    Wave,
+    // cycles through colors
+    Rainbow,
+    // each grapheme brightens in as it's revealed
+    Fade,
+}
+
+// in the order they're drawn, so Fade shows over Rainbow
+const EFFECTS: [Effect; 4] = [Effect::Shake, Effect::Wave, Effect::Rainbow, Effect::Fade];
+
+// Which effects a span has, e.g. both of {fade}{shake}...{/shake}{/fade}.
+// Saved as a list of names.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Vec<Effect>", into = "Vec<Effect>")]
+pub struct Effects(u8);
+
+// How a span of a message looks, drawn over the message's own color.  Unset
+// colors are left as they are.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextStyle {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub dim: bool,
+    pub effects: Effects,
+}
+
+// A byte range of Message::buffer and how it looks.  Ranges may run past the
+// end of the buffer, for text that hasn't been revealed yet.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Span {
+    pub range: Range<usize>,
+    pub style: TextStyle,
+}
+
+// One wrapped line: the byte ranges of the text it shows, and whether a word
+// was split with a hyphen at its end.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Line {
+    pub parts: Vec<Range<usize>>,
+    pub hyphen: bool,
 }
 
 #[derive(Clone)]
-pub enum Name<'a> {
+pub enum Name {
     Player(String),
-    Other(&'a str),
+    Other(String),
 }
 
-impl<'a> Name<'a> {
-    pub fn chars(&self) -> ::std::str::Chars {
-        match self {
-            &Name::Player(ref x) => x.chars(),
-            &Name::Other(ref x) => x.chars(),
+impl Name {
+    pub fn as_str(&self) -> &str {
+        match *self {
+            Name::Player(ref x) => x,
+            Name::Other(ref x) => x,
         }
     }
 }
 
-impl<'a> Message<'a> {
-    pub fn format_log(&self, width: usize) -> Vec<String> {
-        let mut lines: Vec<String> = Vec::new();
-        if width == 0 {
-            return lines;
-        }
-        let mut line = format!("{reset}<{color}",
-                               reset = color::Fg(color::Reset),
-                               color = color::Fg(self.color));
-        let mut used = 1;
-
-        for c in self.name.chars() {
-            if used == width {
-                lines.push(line);
-                line = format!("{color}", color = color::Fg(self.color));
-                used = 0;
+const RAINBOW: [u8; 6] = [196, 208, 226, 46, 33, 129];
+// grays a freshly revealed grapheme steps through, a frame each
+const FADE: [u8; 4] = [236, 240, 244, 249];
+
+impl Effect {
+    pub fn from_name(name: &str) -> Option<Effect> {
+        Some(match name {
+            "shake" => Effect::Shake,
+            "wave" => Effect::Wave,
+            "rainbow" => Effect::Rainbow,
+            "fade" => Effect::Fade,
+            _ => return None,
+        })
+    }
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+    // Moves or recolors the `k`th grapheme of a message, on top of any other
+    // effects.  `age` is frames since it was revealed.  With no frame the
+    // effect is at rest.
+    fn apply(&self, glyph: &mut Glyph, k: usize, frame: Option<u64>, age: u64) {
+        match (*self, frame) {
+            (Effect::Shake, Some(f)) => {
+                let n = noise(f / 2, k as u64);
+                glyph.offset.0 += (n % 3) as i16 - 1;
+                glyph.offset.1 += if n.is_multiple_of(11) { -1 } else { 0 };
             }
-            line.push(c);
-            used += 1;
-        }
-        if used == width {
-            lines.push(line);
-            line = format!("{reset}>{color}",
-                           reset = color::Fg(color::Reset),
-                           color = color::Fg(self.color));
-            used = 1;
-        } else {
-            line = format!("{}{reset}>{color}",
-                           line,
-                           reset = color::Fg(color::Reset),
-                           color = color::Fg(self.color));
-            used += 1;
-        }
-        for c in ::std::iter::once(&' ').chain(self.buffer.iter()) {
-            if used == width {
-                lines.push(line);
-                line = format!("{color}", color = color::Fg(self.color));
-                used = 0;
+            (Effect::Wave, Some(f)) => {
+                let phase = k as f32 * 0.7 - f as f32 * 0.4;
+                glyph.offset.1 += phase.sin().round() as i16;
+            }
+            (Effect::Rainbow, f) => {
+                let step = k + f.unwrap_or(0) as usize;
+                glyph.style.fg = Color::Ansi(RAINBOW[step % RAINBOW.len()]);
             }
-            line.push(*c);
-            used += 1;
+            (Effect::Fade, Some(_)) if (age as usize) < FADE.len() => {
+                glyph.style.fg = Color::Ansi(FADE[age as usize]);
+            }
+            _ => {}
         }
-        lines.push(line);
-        lines
     }
-    pub fn format_buffer(&self, width: usize) -> String {
-        let mut used = 0;
-        let mut result = String::with_capacity(width + 10);
-        for c in ::std::iter::once(&' ').chain(self.buffer.iter()).rev() {
-            if used == width {
-                return format!("{color}{}", result, color = color::Fg(self.color));
+}
+
+impl Effects {
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+    pub fn contains(&self, effect: Effect) -> bool {
+        self.0 & effect.bit() != 0
+    }
+    pub fn insert(&mut self, effect: Effect) {
+        self.0 |= effect.bit();
+    }
+    // those in either
+    pub fn union(&self, other: Effects) -> Effects {
+        Effects(self.0 | other.0)
+    }
+    pub fn to_vec(&self) -> Vec<Effect> {
+        EFFECTS.iter().cloned().filter(|&x| self.contains(x)).collect()
+    }
+}
+
+impl FromIterator<Effect> for Effects {
+    fn from_iter<I: IntoIterator<Item = Effect>>(iter: I) -> Self {
+        let mut effects = Effects::default();
+        for effect in iter {
+            effects.insert(effect);
+        }
+        effects
+    }
+}
+
+impl From<Vec<Effect>> for Effects {
+    fn from(effects: Vec<Effect>) -> Self {
+        effects.into_iter().collect()
+    }
+}
+
+impl From<Effects> for Vec<Effect> {
+    fn from(effects: Effects) -> Self {
+        effects.to_vec()
+    }
+}
+
+// a well mixed hash of two numbers, for shaking
+fn noise(a: u64, b: u64) -> u64 {
+    let mut x = a.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ b.wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+    x ^= x >> 29;
+    x = x.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x ^ (x >> 32)
+}
+
+impl TextStyle {
+    pub fn is_plain(&self) -> bool {
+        *self == TextStyle::default()
+    }
+    pub fn over(&self, base: Style) -> Style {
+        Style {
+            fg: self.fg.unwrap_or(base.fg),
+            bg: self.bg.unwrap_or(base.bg),
+            bold: base.bold || self.bold,
+            italic: base.italic || self.italic,
+            underline: base.underline || self.underline,
+            dim: base.dim || self.dim,
+            invert: base.invert,
+        }
+    }
+}
+
+impl Line {
+    // the line's text without any styling
+    pub fn text(&self, text: &str) -> String {
+        let mut line: String = self.parts.iter().map(|r| &text[r.clone()]).collect();
+        if self.hyphen {
+            line.push('-');
+        }
+        line
+    }
+    fn push(&mut self, range: Range<usize>) {
+        if let Some(last) = self.parts.last_mut() {
+            if last.end == range.start {
+                last.end = range.end;
+                return;
             }
-            result.insert(0, *c);
-            used += 1;
-        }
-        if used == width {
-            return format!("{color}{}", result, color = color::Fg(self.color));
-        }
-        result = format!("{reset}>{color}{}",
-                         result,
-                         reset = color::Fg(color::Reset),
-                         color = color::Fg(self.color));
-        used += 1;
-        for c in self.name.chars().rev() {
-            if used == width {
-                return format!("{color}{}", result, color = color::Fg(self.color));
+        }
+        self.parts.push(range);
+    }
+}
+
+impl Message {
+    // Styles the message for `markup`, and keeps it to be saved with the
+    // log.  Reveal the returned text into the buffer.
+    pub fn set_markup(&mut self, markup: &str) -> Marked {
+        let marked = Marked::parse(markup);
+        self.spans = marked.spans();
+        self.markup = Some(markup.to_owned());
+        marked
+    }
+
+    // Adds text in a style of its own.  A span carrying on from the last one
+    // in the same style extends it.
+    pub fn push_styled(&mut self, text: &str, style: TextStyle) {
+        let start = self.buffer.len();
+        self.buffer.push_str(text);
+        let end = self.buffer.len();
+        if style.is_plain() || start == end {
+            return;
+        }
+        if let Some(last) = self.spans.last_mut() {
+            if last.range.end == start && last.style == style {
+                last.range.end = end;
+                return;
+            }
+        }
+        self.spans.push(Span {
+            range: start..end,
+            style,
+        });
+    }
+
+    fn base_style(&self) -> Style {
+        Style { fg: self.color, ..Style::default() }
+    }
+
+    // Notes that the buffer has been drawn up to its current length, for
+    // Effect::Fade.
+    pub fn stamp(&mut self, frame: u64) {
+        let len = self.buffer.len();
+        if self.reveals.last().map(|x| x.0).unwrap_or(0) < len {
+            self.reveals.push((len, frame));
+        }
+    }
+
+    // whether it looks any different from one frame to the next
+    pub fn is_animated(&self) -> bool {
+        !self.frozen && self.spans.iter().any(|x| !x.style.effects.is_empty())
+    }
+
+    // the style at byte `at` of the buffer, later spans on top, and the
+    // effects of every span there
+    fn style_at(&self, at: usize) -> (Style, Effects) {
+        self.spans
+            .iter()
+            .filter(|x| x.range.start <= at && at < x.range.end)
+            .fold((self.base_style(), Effects::default()),
+                  |(style, effects), x| (x.style.over(style), effects.union(x.style.effects)))
+    }
+
+    // A wrapped line's graphemes in their spans' styles and effects.  `k`
+    // counts graphemes through the message.
+    fn line_glyphs(&self, line: &Line, frame: Option<u64>, k: &mut usize) -> Vec<Glyph> {
+        let mut glyphs: Vec<Glyph> = Vec::new();
+        for range in line.parts.iter() {
+            for (i, g) in self.buffer[range.clone()].grapheme_indices(true) {
+                let at = range.start + i;
+                let (style, effects) = self.style_at(at);
+                let mut glyph = Glyph::new(g, style);
+                if !effects.is_empty() {
+                    let shown = self.reveals
+                        .iter()
+                        .find(|x| x.0 > at)
+                        .map(|x| x.1)
+                        .or(frame)
+                        .unwrap_or(0);
+                    let age = frame.unwrap_or(0).saturating_sub(shown);
+                    for effect in effects.to_vec() {
+                        effect.apply(&mut glyph, *k, frame, age);
+                    }
+                }
+                glyphs.push(glyph);
+                *k += 1;
             }
-            result.insert(0, c);
-            used += 1;
         }
-        if used == width {
-            format!("{color}{}", result, color = color::Fg(self.color))
+        if line.hyphen {
+            let style = glyphs.last().map(|x| x.style).unwrap_or(self.base_style());
+            glyphs.push(Glyph::new("-", style));
+        }
+        glyphs
+    }
+
+    // "<Name> " for the first line, cut short with … if the name won't fit
+    fn name_tag(&self, width: usize, theme: &Theme) -> (Vec<Glyph>, usize) {
+        let name = self.name.as_str();
+        let brackets = str_width(&theme.name_open) + str_width(&theme.name_close);
+        let room = width.saturating_sub(brackets + 2);
+        let shown = if str_width(name) > room + 1 {
+            let mut shown = String::new();
+            let mut used = 0;
+            for g in name.graphemes(true) {
+                used += str_width(g);
+                if used > room {
+                    break;
+                }
+                shown.push_str(g);
+            }
+            shown.push('…');
+            shown
         } else {
-            format!("{reset}<{color}{}",
-                    result,
-                    reset = color::Fg(color::Reset),
-                    color = color::Fg(self.color))
+            name.to_owned()
+        };
+        let plain = theme.text_style();
+        let mut tag: Vec<Glyph> =
+            theme.name_open.graphemes(true).map(|g| Glyph::new(g, plain)).collect();
+        tag.extend(shown.graphemes(true).map(|g| Glyph::new(g, self.base_style())));
+        tag.extend(theme.name_close.graphemes(true).map(|g| Glyph::new(g, plain)));
+        tag.push(Glyph::new(" ", Style::default()));
+        (tag, str_width(&shown) + brackets + 1)
+    }
+
+    // Word wrapped lines, continuation lines indented to line up after the
+    // name tag.  Effects are as of `frame`, or at rest for None.
+    fn layout(&self, width: usize, frame: Option<u64>, theme: &Theme) -> Vec<Vec<Glyph>> {
+        if width == 0 {
+            return Vec::new();
+        }
+        let (tag, tag_width) = self.name_tag(width, theme);
+        // a hanging indent that eats most of the line isn't worth it
+        let indent = if tag_width <= width / 2 { tag_width } else { 0 };
+        let mut k = 0;
+        wrap(&self.buffer, width, tag_width, indent)
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let mut glyphs = if i == 0 {
+                    tag.clone()
+                } else {
+                    vec![Glyph::new(" ", Style::default()); indent]
+                };
+                glyphs.extend(self.line_glyphs(line, frame, &mut k));
+                glyphs
+            })
+            .collect()
+    }
+
+    // format_log's lines as glyphs, e.g. for exporting.  With no width,
+    // lines only break where the text does.
+    pub fn rest_glyphs(&self, width: Option<usize>, theme: &Theme) -> Vec<Vec<Glyph>> {
+        // wide enough to never wrap, with room to add to
+        self.layout(width.unwrap_or(usize::MAX / 2), None, theme)
+    }
+
+    // format_log with its effects as of `frame`, unless frozen
+    pub fn glyph_log(&self, width: usize, frame: u64, theme: &Theme) -> Vec<Vec<Glyph>> {
+        self.layout(width, if self.frozen { None } else { Some(frame) }, theme)
+    }
+
+    // Word wrapped lines with effects at rest, each back to plain at the
+    // end so nothing bleeds into the next.
+    pub fn format_log(&self, width: usize, theme: &Theme) -> Vec<String> {
+        self.layout(width, None, theme).iter().map(|x| screen::sgr_line(x)).collect()
+    }
+    // The line being typed into, as one line: the grapheme at `cursor` is
+    // drawn inverted, and the view scrolls to keep it in sight
+    pub fn format_input(&self, width: usize, cursor: usize, theme: &Theme) -> String {
+        // (grapheme, colored)
+        let mut cells: Vec<(&str, bool)> =
+            theme.name_open.graphemes(true).map(|g| (g, false)).collect();
+        cells.extend(self.name.as_str().graphemes(true).map(|g| (g, true)));
+        cells.extend(theme.name_close.graphemes(true).map(|g| (g, false)));
+        cells.push((" ", true));
+        let buffer_start = cells.len();
+        cells.extend(self.buffer.graphemes(true).map(|g| (g, true)));
+        // room for the cursor past the end
+        cells.push((" ", true));
+        let cursor = ::std::cmp::min(buffer_start + cursor, cells.len() - 1);
+
+        // as much of the end as fits, unless that would hide the cursor
+        let mut start = cells.len();
+        let mut used = 0;
+        while start > 0 && used + str_width(cells[start - 1].0) <= width {
+            start -= 1;
+            used += str_width(cells[start].0);
+        }
+        if cursor < start {
+            start = cursor;
+        }
+        let mut result = String::with_capacity(width + 20);
+        let mut colored = None;
+        let mut pen = Style::default();
+        let mut used = 0;
+        for (i, &(g, is_colored)) in cells.iter().enumerate().skip(start) {
+            used += str_width(g);
+            if used > width {
+                break;
+            }
+            if colored != Some(is_colored) {
+                let to = if is_colored {
+                    self.base_style()
+                } else {
+                    theme.text_style()
+                };
+                result.push_str(&pen.sgr_to(&to));
+                pen = to;
+                colored = Some(is_colored);
+            }
+            if i == cursor {
+                result.push_str(&format!("{}{}{}", style::Invert, g, style::NoInvert));
+            } else {
+                result.push_str(g);
+            }
         }
+        result
+    }
+}
+
+// Columns a string takes up on screen: CJK and most emoji are two wide,
+// combining marks are zero.
+pub fn str_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+// Wraps text at spaces and newlines to `width` columns.  The first line
+// starts at column `first`, the rest at `indent`.  Words too long for a line
+// of their own are split with a hyphen, between graphemes.
+pub fn wrap(text: &str, width: usize, first: usize, indent: usize) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut line = Line::default();
+    let mut col = first;
+    let mut para_start = 0;
+    for (p, para) in text.split('\n').enumerate() {
+        if p > 0 {
+            lines.push(mem::take(&mut line));
+            col = indent;
+        }
+        let mut spaces = 0;
+        let mut word_start = para_start;
+        for (w, word) in para.split(' ').enumerate() {
+            if w > 0 {
+                spaces += 1;
+            }
+            let start = word_start;
+            word_start += word.len() + 1;
+            if word.is_empty() {
+                continue;
+            }
+            // (byte offset in text, grapheme)
+            let mut word: Vec<(usize, &str)> =
+                word.grapheme_indices(true).map(|(i, g)| (start + i, g)).collect();
+            let mut word_width: usize = word.iter().map(|&(_, g)| str_width(g)).sum();
+            if col + spaces + word_width > width && col > indent {
+                let room = width.saturating_sub(col + spaces + 1);
+                // only split here if the word won't fit on a line of its own
+                if word_width <= width.saturating_sub(indent) || room < 2 {
+                    lines.push(mem::take(&mut line));
+                    col = indent;
+                    spaces = 0;
+                }
+            }
+            while col + spaces + word_width > width {
+                let room = width.saturating_sub(col + spaces + 1);
+                // as many whole graphemes as fit before the hyphen
+                let mut n = 0;
+                let mut used = 0;
+                while n < word.len() && used + str_width(word[n].1) <= room {
+                    used += str_width(word[n].1);
+                    n += 1;
+                }
+                let mut hyphen = true;
+                if n == 0 {
+                    if col > indent || spaces > 0 {
+                        lines.push(mem::take(&mut line));
+                        col = indent;
+                        spaces = 0;
+                        continue;
+                    }
+                    // not even one grapheme and a hyphen fit on a fresh
+                    // line, so break between graphemes without one, with at
+                    // least one on each line
+                    hyphen = false;
+                    n = 1;
+                    used = str_width(word[0].1);
+                    while n < word.len() && used + str_width(word[n].1) <= width - col {
+                        used += str_width(word[n].1);
+                        n += 1;
+                    }
+                    if n == word.len() {
+                        break;
+                    }
+                }
+                let (at, last) = (word[0].0, word[n - 1]);
+                line.push(at - spaces..last.0 + last.1.len());
+                line.hyphen = hyphen;
+                lines.push(mem::take(&mut line));
+                col = indent;
+                spaces = 0;
+                word = word.split_off(n);
+                word_width -= used;
+            }
+            let (at, last) = (word[0].0, word[word.len() - 1]);
+            line.push(at - spaces..last.0 + last.1.len());
+            col += spaces + word_width;
+            spaces = 0;
+        }
+        para_start += para.len() + 1;
+    }
+    lines.push(line);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use markup::Marked;
+    use super::{wrap, Effect, Effects, Message, Name, FADE};
+    use term::{Color, Theme};
+
+    fn said(markup: &str) -> Message {
+        let marked = Marked::parse(markup);
+        Message {
+            name: Name::Other("Robot".to_owned()),
+            speaker: None,
+            markup: None,
+            time: SystemTime::now(),
+            color: Color::Ansi(226),
+            buffer: marked.text(),
+            spans: marked.spans(),
+            reveals: Vec::new(),
+            frozen: false,
+        }
+    }
+
+    #[test]
+    fn nested_effects_all_apply() {
+        let mut msg = said("{fade}ab{shake}cd{/shake}ef{/fade}");
+        let both: Effects = vec![Effect::Fade, Effect::Shake].into();
+        assert_eq!(msg.spans[1].style.effects, both);
+        assert_eq!(msg.spans[2].style.effects, vec![Effect::Fade].into());
+        msg.stamp(10);
+        let glyphs = &msg.glyph_log(40, 10, &Theme::default())[0];
+        // after "<Robot> "
+        let text: Vec<&str> = glyphs[8..].iter().map(|x| x.text.as_str()).collect();
+        assert_eq!(text, vec!["a", "b", "c", "d", "e", "f"]);
+        for g in glyphs[8..].iter() {
+            assert_eq!(g.style.fg, Color::Ansi(FADE[0]));
+        }
+        // the shake is there too, on some frame or other
+        let shaken = (10..100).any(|f| {
+            msg.glyph_log(40, f, &Theme::default())[0][10..12].iter().any(|x| x.offset != (0, 0))
+        });
+        assert!(shaken);
+    }
+
+    #[test]
+    fn effects_save_as_names() {
+        let effects: Effects = vec![Effect::Wave, Effect::Rainbow].into();
+        let json = ::serde_json::to_string(&effects).unwrap();
+        assert_eq!(json, "[\"wave\",\"rainbow\"]");
+        assert_eq!(::serde_json::from_str::<Effects>(&json).unwrap(), effects);
+    }
+
+    fn wrapped(text: &str, width: usize, first: usize, indent: usize) -> Vec<String> {
+        wrap(text, width, first, indent).iter().map(|x| x.text(text)).collect()
+    }
+
+    #[test]
+    fn wraps_at_spaces_and_newlines() {
+        // the space a line breaks at is dropped
+        assert_eq!(wrapped("one two three four", 10, 0, 0), vec!["one two", "three four"]);
+        assert_eq!(wrapped("one two three four", 10, 4, 2),
+                   vec!["one", "two", "three", "four"]);
+        assert_eq!(wrapped("one\ntwo  three", 20, 0, 0), vec!["one", "two  three"]);
+        assert_eq!(wrapped("", 10, 0, 0), vec![""]);
+    }
+
+    #[test]
+    fn splits_long_words_with_a_hyphen() {
+        let lines = wrap("a abcdefghij", 6, 0, 0);
+        assert_eq!(lines.iter().map(|x| x.text("a abcdefghij")).collect::<Vec<_>>(),
+                   vec!["a abc-", "defgh-", "ij"]);
+        assert!(lines[0].hyphen && lines[1].hyphen && !lines[2].hyphen);
+        // the ranges are of the text, hyphens aren't in it
+        assert_eq!((lines[1].parts.len(), lines[1].parts[0].clone()), (1, 5..10));
+        // a word that fits on a line of its own goes there whole
+        assert_eq!(wrapped("ab abcdef", 6, 0, 0), vec!["ab", "abcdef"]);
+        // too narrow for even a letter and a hyphen, so it breaks between
+        // letters without one
+        let lines = wrap("abc", 1, 0, 0);
+        assert_eq!(lines.iter().map(|x| x.text("abc")).collect::<Vec<_>>(),
+                   vec!["a", "b", "c"]);
+        assert!(lines.iter().all(|x| !x.hyphen));
+        assert_eq!(wrapped("a bc", 1, 0, 0), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn wide_graphemes_are_not_split() {
+        // each is two columns, and é is e and a combining accent
+        assert_eq!(wrapped("世界世界世界", 7, 0, 0), vec!["世界世-", "界世界"]);
+        assert_eq!(wrapped("e\u{301}e\u{301}e\u{301}e\u{301}", 3, 0, 0),
+                   vec!["e\u{301}e\u{301}-", "e\u{301}e\u{301}"]);
+        assert_eq!(wrapped("世世", 3, 0, 0), vec!["世-", "世"]);
+        assert_eq!(wrapped("世世", 2, 0, 0), vec!["世", "世"]);
+        // one wider than the line still goes on a line of its own
+        assert_eq!(wrapped("a世b", 1, 0, 0), vec!["a", "世", "b"]);
+        assert_eq!(wrapped("世界", 1, 0, 0), vec!["世", "界"]);
     }
 }
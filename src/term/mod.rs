@@ -1,143 +1,450 @@
 
 pub mod message;
-pub use self::message::{Name, Message};
+pub use self::message::{Name, Message, TextStyle, Span, Effect, Effects};
+pub mod input;
+pub use self::input::{Input, Inputs};
+pub mod scroll;
+pub use self::scroll::Scrollback;
+pub mod screen;
+pub use self::screen::{Color, ColorMode, Screen, Style};
+pub mod layout;
+pub use self::layout::{Layout, Pane, Rect, Size};
+pub mod portrait;
+pub use self::portrait::Portrait;
+pub mod backend;
+pub use self::backend::{Backend, RawBackend, VirtualTerminal};
+pub mod record;
+pub use self::record::Record;
+pub mod export;
+pub mod theme;
+pub use self::theme::Theme;
 
+use std::collections::HashMap;
 use std::error::Error;
 use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Instant, SystemTime};
 
 use termion;
 // use termion::{color, cursor};
 use termion::raw::IntoRawMode;
+use termion::input::MouseTerminal;
+use termion::event::{Key, MouseEvent, MouseButton};
+use unicode_segmentation::UnicodeSegmentation;
 
-pub struct Term<'a> {
-    out: termion::raw::RawTerminal<io::Stdout>,
-    pub log: Vec<Message<'a>>,
-    pub msg_buffer: Option<Message<'a>>,
+use self::message::str_width;
+
+// lines moved per mouse wheel step
+const WHEEL_LINES: usize = 3;
+// length of a frame of the text effects clock
+const FRAME_MILLIS: u64 = 50;
+
+// Draws to a real terminal by default, or to any other Backend, e.g. a
+// VirtualTerminal to read the screen back from.
+pub struct Term<B: Backend = RawBackend> {
+    out: B,
+    pub log: Vec<Message>,
+    pub scrollback: Scrollback,
+    pub msg_buffer: Option<Message>,
     pub user_name: String,
-    pub user_buffer: Vec<char>,
+    pub user_buffer: String,
+    // insert point in user_buffer, in graphemes
+    pub user_cursor: usize,
+    // what's on the terminal, so draw() only sends what changed
+    screen: Screen,
+    // text effects stop once a message goes in the log
+    pub freeze_log: bool,
+    // the frame being drawn, counted from `start`
+    pub frame: u64,
+    start: Instant,
+    pub layout: Layout,
+    // the active speaker, for Pane::Portrait
+    pub portrait: Option<Portrait>,
+    // how loud they are right now, e.g. from audio::Mixer::level
+    pub loudness: f32,
+    // shown at the left of the status bar
+    pub status: String,
+    theme: Theme,
 }
 
-impl<'a> Term<'a> {
+impl Term {
     pub fn init() -> Result<Self, Box<Error>> {
-        let stdout = io::stdout().into_raw_mode()?;
+        let stdout = MouseTerminal::from(io::stdout().into_raw_mode()?);
+        Ok(Term::with_backend(stdout)?)
+    }
+}
+
+impl<B: Backend> Term<B> {
+    pub fn with_backend(out: B) -> Result<Self, io::Error> {
         let mut term = Term {
-            out: stdout,
+            out,
             log: Vec::new(),
+            scrollback: Scrollback::new(),
             msg_buffer: None,
-            user_buffer: Vec::new(),
+            user_buffer: String::new(),
+            user_cursor: 0,
             user_name: String::new(),
+            screen: Screen::new(0, 0),
+            freeze_log: true,
+            frame: 0,
+            start: Instant::now(),
+            layout: Layout::default(),
+            portrait: None,
+            loudness: 0.0,
+            status: String::new(),
+            theme: Theme::default(),
         };
         write!(term, "{}{}", termion::clear::All, termion::cursor::Hide)?;
         term.flush()?;
         Ok(term)
     }
+    pub fn backend(&self) -> &B {
+        &self.out
+    }
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.out
+    }
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+    // the log is reformatted in the new theme on the next draw
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.scrollback.invalidate();
+    }
+    // Sends colors as `mode` can show them, in place of what was detected
+    // from the environment.
+    pub fn set_color_mode(&mut self, mode: ColorMode) {
+        self.screen.colors = mode;
+        self.screen.forget();
+    }
+    // Whether frames are sent as synchronized output (mode 2026), for
+    // terminals known to support it.
+    pub fn set_sync(&mut self, sync: bool) {
+        self.screen.sync = sync;
+    }
+    // the next draw starts from a blank screen
     pub fn clear(&mut self) -> Result<(), io::Error> {
-        write!(self, "{}", termion::clear::All)
+        self.screen.forget();
+        Ok(())
     }
     pub fn draw(&mut self) -> Result<(), io::Error> {
-        self.draw_borders()?;
+        let bounds = self.bounds();
+        self.screen.resize(bounds[1].wrapping_sub(bounds[0]),
+                           bounds[3].wrapping_sub(bounds[2]));
+        self.screen.clear();
+        let elapsed = self.start.elapsed();
+        self.frame = (elapsed.as_secs() * 1000 + elapsed.subsec_nanos() as u64 / 1_000_000) /
+                     FRAME_MILLIS;
+        if let Some(ref mut msg) = self.msg_buffer {
+            msg.stamp(self.frame);
+        }
+        // the log first, so the status note sees the scrollback it updates
         self.draw_log()?;
+        self.draw_status()?;
+        self.draw_portrait()?;
         self.draw_msg_buffer()?;
         self.draw_user_buffer()?;
+        self.screen.present(&mut self.out)?;
         self.flush()
     }
     pub fn bounds(&self) -> [u16; 4] {
-        if let Ok((w, h)) = termion::terminal_size() {
+        if let Ok((w, h)) = self.out.size() {
             [0, w, 0, h]
         } else {
             [0; 4]
         }
     }
+    // where `pane` is on screen, if the layout has it at all
+    pub fn region(&self, pane: Pane) -> Option<Rect> {
+        let bounds = self.bounds();
+        let screen = Rect {
+            x: bounds[0] + 1,
+            y: bounds[2] + 1,
+            width: bounds[1].saturating_sub(bounds[0]),
+            height: bounds[3].saturating_sub(bounds[2]),
+        };
+        let mut rect = self.layout.regions(screen).remove(&pane)?;
+        if pane == Pane::Log {
+            let (left, right) = self.theme.log_margin;
+            rect.x += ::std::cmp::min(left, rect.width);
+            rect.width = rect.width.saturating_sub(left).saturating_sub(right);
+        }
+        Some(rect).filter(|x| !x.is_empty())
+    }
     pub fn msg_done(&mut self) {
-        if let Some(msg) = self.msg_buffer.take() {
+        if let Some(mut msg) = self.msg_buffer.take() {
+            msg.frozen = self.freeze_log;
             self.log.push(msg);
         }
     }
+    // the log as it would be saved
+    pub fn records(&self) -> Vec<Record> {
+        self.log.iter().map(Record::from_message).collect()
+    }
+    pub fn save_log<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<Error>> {
+        record::save(path, &self.records())
+    }
+    // Writes a transcript of the log, as HTML, ANSI or plain text by the
+    // file's extension (see export::Format).  With a width, it's wrapped the
+    // same as on screen.
+    pub fn export_log<P: AsRef<Path>>(&self,
+                                      path: P,
+                                      width: Option<usize>)
+                                      -> Result<(), Box<Error>> {
+        export::save(path, &self.log, width, &self.theme)
+    }
+    // Replaces the log with a saved one, e.g. from record::load, scrolled to
+    // the bottom.
+    pub fn restore_log(&mut self, records: Vec<Record>) {
+        self.log = records.iter().map(Record::message).collect();
+        self.scrollback.invalidate();
+    }
     pub fn user_msg_done(&mut self) {
         let msg = Message {
             name: Name::Player(self.user_name.clone()),
-            color: termion::color::AnsiValue::rgb(0, 0, 5),
+            speaker: None,
+            markup: None,
+            time: SystemTime::now(),
+            color: self.theme.player_color,
             buffer: self.user_buffer.drain(..).collect(),
+            spans: Vec::new(),
+            reveals: Vec::new(),
+            frozen: false,
         };
+        self.user_cursor = 0;
         self.log.push(msg);
 
     }
 
-    pub fn draw_borders(&mut self) -> Result<(), io::Error> {
-        let bounds = self.bounds();
-        let width = bounds[1].wrapping_sub(bounds[0]);
-        let bg = termion::color::Bg(termion::color::AnsiValue::rgb(1, 1, 1));
-        let text: String = ::std::iter::once(' ').cycle().take(width as usize).collect();
-
-        write!(self.out, "{goto}{clear}{bg}{}{bgr}", 
-                       text,
-                       goto = termion::cursor::Goto(1, bounds[3].wrapping_sub(2)),
-                       clear = termion::clear::CurrentLine,
-                       bg = bg,
-                       bgr = termion::color::Bg(termion::color::Reset),
-                       )?;
-        Ok(())
+    // Routes input to the input line or the log.  Returns false for input
+    // it doesn't use.
+    pub fn handle_input(&mut self, input: Input) -> bool {
+        match input {
+            Input::Key(k) => self.handle_key(k),
+            Input::Mouse(MouseEvent::Press(MouseButton::WheelUp, _, _)) => {
+                self.scrollback.up(WHEEL_LINES);
+                true
+            }
+            Input::Mouse(MouseEvent::Press(MouseButton::WheelDown, _, _)) => {
+                self.scrollback.down(WHEEL_LINES);
+                true
+            }
+            Input::Mouse(_) => false,
+            Input::Resize(..) => {
+                self.resize();
+                true
+            }
+        }
     }
-    pub fn draw_log(&mut self) -> Result<(), io::Error> {
-        let bounds = self.bounds();
-        let width = bounds[1].wrapping_sub(bounds[0]);
-        if width < 11 || bounds[2] + 6 > bounds[3] {
-            return Ok(());
+
+    // Everything moves when the terminal changes size, so the next draw
+    // starts from a blank screen.  The log is rewrapped if the width changed.
+    pub fn resize(&mut self) {
+        self.screen.forget();
+        if self.log_width() != self.scrollback.width() {
+            self.scrollback.invalidate();
         }
-        let mut current = bounds[3].wrapping_sub(5);
-        for msg in self.log.iter().rev() {
-            for line in msg.format_log(width.wrapping_sub(10) as usize).iter().rev() {
-                write!(self.out, "{goto}{clear}{}", 
-                       line,
-                       clear = termion::clear::CurrentLine,
-                       goto = termion::cursor::Goto(5, current),
-                       )?;
-                if current == bounds[2] {
-                    return Ok(());
+    }
+
+    // Edits the input line, or scrolls the log for PageUp/PageDown.  Home
+    // and End move the cursor, or when it's already there, jump to the top
+    // or bottom of the log.  Returns false for keys it doesn't use.
+    pub fn handle_key(&mut self, key: Key) -> bool {
+        let len = self.user_buffer.graphemes(true).count();
+        if self.user_cursor > len {
+            self.user_cursor = len;
+        }
+        match key {
+            Key::Char('\n') => {
+                if !self.user_buffer.is_empty() {
+                    self.user_msg_done();
+                }
+            }
+            Key::Char(c) if !c.is_control() => {
+                let at = self.user_byte(self.user_cursor);
+                self.user_buffer.insert(at, c);
+                // a combining mark joins the grapheme before it instead of
+                // starting a new one
+                let end = at + c.len_utf8();
+                self.user_cursor = self.user_buffer[..end].graphemes(true).count();
+            }
+            Key::Backspace => {
+                if self.user_cursor > 0 {
+                    self.user_cursor -= 1;
+                    let (at, end) = (self.user_byte(self.user_cursor),
+                                     self.user_byte(self.user_cursor + 1));
+                    self.user_buffer.drain(at..end);
+                }
+            }
+            Key::Delete => {
+                if self.user_cursor < len {
+                    let (at, end) = (self.user_byte(self.user_cursor),
+                                     self.user_byte(self.user_cursor + 1));
+                    self.user_buffer.drain(at..end);
+                }
+            }
+            Key::Left => self.user_cursor = self.user_cursor.saturating_sub(1),
+            Key::Right => {
+                if self.user_cursor < len {
+                    self.user_cursor += 1;
                 }
-                current -= 1;
+            }
+            Key::Home if self.user_cursor == 0 => self.scrollback.top(),
+            Key::Home => self.user_cursor = 0,
+            Key::End if self.user_cursor == len => self.scrollback.bottom(),
+            Key::End => self.user_cursor = len,
+            Key::PageUp => {
+                let page = self.log_page();
+                self.scrollback.up(page);
+            }
+            Key::PageDown => {
+                let page = self.log_page();
+                self.scrollback.down(page);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    // byte offset of the `cursor`th grapheme in user_buffer
+    fn user_byte(&self, cursor: usize) -> usize {
+        self.user_buffer
+            .grapheme_indices(true)
+            .nth(cursor)
+            .map(|(i, _)| i)
+            .unwrap_or(self.user_buffer.len())
+    }
+
+    pub fn draw_status(&mut self) -> Result<(), io::Error> {
+        let rect = match self.region(Pane::Status) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        let width = rect.width as usize;
+        let theme = &self.theme;
+        let capped = |x: &str| format!("{}{}{}", theme.status_open, x, theme.status_close);
+        let note = if self.scrollback.at_bottom() {
+            String::new()
+        } else {
+            match self.scrollback.unseen(self.log.len()) {
+                0 => capped("more below"),
+                1 => capped("1 new message"),
+                n => capped(&format!("{} new messages", n)),
+            }
+        };
+        // the status sits at the left and the note at the right, if there's
+        // room
+        let mut text = if self.status.is_empty() {
+            String::new()
+        } else {
+            capped(&self.status)
+        };
+        let fill = self.theme.border_fill.to_string();
+        // a zero width fill still has to get somewhere
+        let fill_width = ::std::cmp::max(str_width(&fill), 1);
+        let pad = width.saturating_sub(str_width(&text) + str_width(&note) + 2);
+        text.extend(::std::iter::repeat_n(fill.as_str(), pad / fill_width));
+        text.push_str(&note);
+        // any part of a wide fill past the end is clipped
+        let rest = width.saturating_sub(str_width(&text));
+        text.extend(::std::iter::repeat_n(fill.as_str(), rest.div_ceil(fill_width)));
+
+        let style = Style::default().sgr_to(&self.theme.border_style());
+        for row in rect.y..rect.bottom() + 1 {
+            self.screen.print_clipped(rect.x, row, rect.width, &format!("{}{}", style, text));
+        }
+        Ok(())
+    }
+
+    pub fn draw_portrait(&mut self) -> Result<(), io::Error> {
+        let rect = match self.region(Pane::Portrait) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        if let Some(ref portrait) = self.portrait {
+            let art = portrait.frame(self.loudness);
+            for (row, line) in (rect.y..rect.bottom() + 1).zip(art.lines()) {
+                self.screen.print_clipped(rect.x, row, rect.width, line);
+            }
+        }
+        Ok(())
+    }
+
+    fn log_width(&self) -> usize {
+        self.region(Pane::Log).map(|x| x.width as usize).unwrap_or(0)
+    }
+    fn log_page(&self) -> usize {
+        let height = self.region(Pane::Log).map(|x| x.height as usize).unwrap_or(0);
+        ::std::cmp::max(height, 2) - 1
+    }
+
+    pub fn draw_log(&mut self) -> Result<(), io::Error> {
+        let rect = match self.region(Pane::Log) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        let log_width = rect.width as usize;
+        self.scrollback.update(&self.log, log_width, &self.theme);
+        let lines = self.scrollback.visible_at(rect.height as usize);
+        let first = rect.bottom() + 1 - lines.len() as u16;
+        // messages still animating are laid out fresh each frame
+        let mut animated = HashMap::new();
+        for (row, (m, l)) in (first..).zip(lines) {
+            if self.log[m].is_animated() {
+                let frame = self.frame;
+                let glyphs = animated.entry(m)
+                    .or_insert_with(|| self.log[m].glyph_log(log_width, frame, &self.theme));
+                self.screen.print_glyphs(rect.x, row, rect, &glyphs[l]);
+            } else {
+                self.screen.print(rect.x, row, &self.scrollback.lines(m)[l]);
             }
         }
         Ok(())
     }
 
     pub fn draw_user_buffer(&mut self) -> Result<(), io::Error> {
-        let bounds = self.bounds();
-        let width = bounds[1].wrapping_sub(bounds[0]);
-        // let height = bounds[3].wrapping_sub(bounds[2]);
+        let rect = match self.region(Pane::Input) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
         let msg = Message {
-            name: Name::Other(&self.user_name),
-            color: termion::color::AnsiValue::rgb(0, 0, 5),
+            name: Name::Other(self.user_name.clone()),
+            speaker: None,
+            markup: None,
+            time: SystemTime::now(),
+            color: self.theme.player_color,
             buffer: self.user_buffer.clone(),
+            spans: Vec::new(),
+            reveals: Vec::new(),
+            frozen: false,
         };
-        write!(self.out, "{goto}{clear}{}", 
-               msg.format_buffer(width.wrapping_sub(1) as usize),
-               clear = termion::clear::CurrentLine,
-               goto = termion::cursor::Goto(1, bounds[3].wrapping_sub(1)),
-               )
+        let line = msg.format_input(rect.width as usize, self.user_cursor, &self.theme);
+        self.screen.print(rect.x, rect.y, &line);
+        Ok(())
     }
 
+    // As much of the end of the live message as fits at the bottom of its
+    // pane.  The top row is left for effects to move into, if there's more
+    // than one.
     pub fn draw_msg_buffer(&mut self) -> Result<(), io::Error> {
-        let bounds = self.bounds();
-        let width = bounds[1].wrapping_sub(bounds[0]);
-        if bounds[2] + 2 > bounds[3] {
-            return Ok(());
-        }
+        let rect = match self.region(Pane::Message) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
         if let Some(ref msg) = self.msg_buffer {
-            write!(self.out, "{goto}{clear}{}", 
-               msg.format_buffer(width.wrapping_sub(1) as usize),
-               clear = termion::clear::CurrentLine,
-               goto = termion::cursor::Goto(1, bounds[3].wrapping_sub(3)),
-               )
-        } else {
-            write!(self.out, "{goto}{clear}", 
-               clear = termion::clear::CurrentLine,
-               goto = termion::cursor::Goto(1, bounds[3].wrapping_sub(3)),
-               )
+            let lines = msg.glyph_log(rect.width as usize, self.frame, &self.theme);
+            let room = ::std::cmp::max(rect.height - 1, 1) as usize;
+            let shown = &lines[lines.len().saturating_sub(room)..];
+            let first = rect.bottom() + 1 - shown.len() as u16;
+            for (row, line) in (first..).zip(shown) {
+                self.screen.print_glyphs(rect.x, row, rect, line);
+            }
         }
+        Ok(())
     }
     pub fn cleanup(&mut self) -> Result<(), io::Error> {
-        let (_, h) = termion::terminal_size().unwrap();
+        let (_, h) = self.out.size()?;
         write!(self.out, "{goto}{reset}{curs}\n", goto = termion::cursor::Goto(1, h),
             reset = termion::color::Fg(termion::color::Reset),
             curs = termion::cursor::Show,
@@ -146,7 +453,7 @@ impl<'a> Term<'a> {
     }
 }
 
-impl<'a> io::Write for Term<'a> {
+impl<B: Backend> io::Write for Term<B> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.out.write(buf)
     }
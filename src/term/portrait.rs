@@ -0,0 +1,31 @@
+// A speaker's picture for Pane::Portrait, as ASCII art which may carry SGR
+// color escapes.  While they're talking it switches to the mouth-open frame
+// whenever their voice is loud enough.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Portrait {
+    pub closed: String,
+    pub open: Option<String>,
+    // loudness (see audio::Level) that opens the mouth
+    pub open_level: f32,
+}
+
+impl Default for Portrait {
+    fn default() -> Self {
+        Portrait {
+            closed: String::new(),
+            open: None,
+            open_level: 0.05,
+        }
+    }
+}
+
+impl Portrait {
+    // the frame to show at `loudness`
+    pub fn frame(&self, loudness: f32) -> &str {
+        match self.open {
+            Some(ref open) if loudness >= self.open_level => open,
+            _ => &self.closed,
+        }
+    }
+}
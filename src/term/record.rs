@@ -0,0 +1,172 @@
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde_json;
+use super::{Message, Name, Span};
+use super::screen::{self, Color};
+
+// A message as it's saved, without what only matters while it's on screen.
+// Logs are saved as JSON Lines, one Record per line, oldest first:
+//
+//     {"speaker":"robot","name":"Testing Robot","color":"#ffd700","text":"Hi!","spans":[],"time":1540000000000}
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    // the id of the Speaker, or None for the player
+    #[serde(default)]
+    pub speaker: Option<String>,
+    pub name: String,
+    #[serde(deserialize_with = "screen::deserialize_color",
+            serialize_with = "screen::serialize_color")]
+    pub color: Color,
+    pub text: String,
+    #[serde(default)]
+    pub spans: Vec<Span>,
+    // the text with its tags, if it was marked up
+    #[serde(default)]
+    pub markup: Option<String>,
+    // when it was said, in milliseconds since the Unix epoch
+    pub time: u64,
+}
+
+impl Record {
+    pub fn from_message(msg: &Message) -> Self {
+        let since = msg.time.duration_since(UNIX_EPOCH).unwrap_or(Duration::from_secs(0));
+        Record {
+            speaker: msg.speaker.clone(),
+            name: msg.name.as_str().to_owned(),
+            color: msg.color,
+            text: msg.buffer.clone(),
+            spans: msg.spans.clone(),
+            markup: msg.markup.clone(),
+            time: since.as_secs() * 1000 + since.subsec_nanos() as u64 / 1_000_000,
+        }
+    }
+    // the message back, fully revealed and with its effects at rest
+    pub fn message(&self) -> Message {
+        Message {
+            name: match self.speaker {
+                Some(_) => Name::Other(self.name.clone()),
+                None => Name::Player(self.name.clone()),
+            },
+            speaker: self.speaker.clone(),
+            markup: self.markup.clone(),
+            time: UNIX_EPOCH + Duration::from_millis(self.time),
+            color: self.color,
+            buffer: self.text.clone(),
+            spans: self.spans.clone(),
+            reveals: Vec::new(),
+            frozen: true,
+        }
+    }
+}
+
+pub fn write_records<W: Write>(out: &mut W, records: &[Record]) -> Result<(), Box<Error>> {
+    for record in records {
+        serde_json::to_writer(&mut *out, record)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+// Reads records back, skipping blank lines.  Errors say which line was bad.
+pub fn read_records<R: BufRead>(input: R) -> Result<Vec<Record>, Box<Error>> {
+    let mut records = Vec::new();
+    for (n, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(x) => records.push(x),
+            Err(e) => return Err(From::from(format!("log line {}: {}", n + 1, e))),
+        }
+    }
+    Ok(records)
+}
+
+// writes a whole log, replacing whatever was at `path`
+pub fn save<P: AsRef<Path>>(path: P, records: &[Record]) -> Result<(), Box<Error>> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_records(&mut out, records)?;
+    out.flush()?;
+    Ok(())
+}
+
+// Adds to the end of a saved log, so a session is kept as it goes even if
+// the game never exits cleanly.
+pub fn append<P: AsRef<Path>>(path: P, records: &[Record]) -> Result<(), Box<Error>> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut out = BufWriter::new(file);
+    write_records(&mut out, records)?;
+    out.flush()?;
+    Ok(())
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<Record>, Box<Error>> {
+    read_records(BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use markup::Marked;
+    use super::{read_records, write_records, Record};
+    use term::{Color, Effect};
+
+    fn said(speaker: Option<&str>, markup: &str) -> Record {
+        let marked = Marked::parse(markup);
+        Record {
+            speaker: speaker.map(|x| x.to_owned()),
+            name: "Testing Robot".to_owned(),
+            color: Color::Rgb(0xff, 0xd7, 0x00),
+            text: marked.text(),
+            spans: marked.spans(),
+            markup: Some(markup.to_owned()),
+            time: 1_540_000_000_000,
+        }
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let records = vec![said(Some("robot"),
+                                "{fade}So {b}very{/b} {shake}angry{/shake}{/fade}!"),
+                           said(None, "{color #5f87ff}plain{/color} words"),
+                           Record {
+                               markup: None,
+                               color: Color::Ansi(12),
+                               ..said(None, "")
+                           }];
+        assert_eq!(records[0].spans[3].style.effects,
+                   vec![Effect::Shake, Effect::Fade].into());
+        let mut out = Vec::new();
+        write_records(&mut out, &records).unwrap();
+        assert_eq!(out.iter().filter(|&&x| x == b'\n').count(), 3);
+        assert_eq!(read_records(&out[..]).unwrap(), records);
+        // and back into a message the same
+        let msg = records[0].message();
+        assert_eq!(msg.speaker, Some("robot".to_owned()));
+        assert_eq!(msg.markup, records[0].markup);
+        assert_eq!(Record::from_message(&msg), records[0]);
+    }
+
+    #[test]
+    fn old_records_load() {
+        // from before spans and markup were saved
+        let line = r#"{"speaker":"robot","name":"Robot","color":[5,5,0],"text":"Hi!","time":1}"#;
+        let records = read_records(line.as_bytes()).unwrap();
+        assert_eq!(records[0].color, Color::cube(5, 5, 0));
+        assert!(records[0].spans.is_empty());
+        assert_eq!(records[0].markup, None);
+    }
+
+    #[test]
+    fn bad_lines_are_named() {
+        let mut out = Vec::new();
+        write_records(&mut out, &[said(None, "fine")]).unwrap();
+        out.extend_from_slice(b"\n{\"name\": 3}\n");
+        let err = read_records(&out[..]).unwrap_err().to_string();
+        assert!(err.starts_with("log line 3: "), "{}", err);
+    }
+}
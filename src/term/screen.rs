@@ -0,0 +1,749 @@
+use std::env;
+use std::io::{self, Write};
+
+use serde::{Deserialize, Deserializer, Serializer};
+use serde::de::Error;
+use termion::{clear, cursor};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::layout::Rect;
+use super::message::str_width;
+
+// Synchronized output: the terminal holds off repainting between these, so a
+// frame never shows half drawn.  Terminals without it ignore them.
+const SYNC_BEGIN: &str = "\x1b[?2026h";
+const SYNC_END: &str = "\x1b[?2026l";
+const SGR_RESET: &str = "\x1b[0m";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Color {
+    #[default]
+    Default,
+    Ansi(u8),
+    // 24-bit, shown as the nearest Ansi color where the terminal can't
+    Rgb(u8, u8, u8),
+}
+
+// How many colors the terminal can show.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorMode {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Style {
+    pub fg: Color,
+    pub bg: Color,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub invert: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cell {
+    // one grapheme, or empty for the right half of a wide one
+    pub text: String,
+    pub style: Style,
+}
+
+// A grapheme to draw, nudged `offset` (columns, rows) from where it would
+// otherwise go.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Glyph {
+    pub text: String,
+    pub style: Style,
+    pub offset: (i16, i16),
+}
+
+// An off-screen copy of the terminal.  Each frame is drawn into it from
+// scratch, then present() sends only the cells that differ from what's
+// already showing.
+pub struct Screen {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    // what the terminal has now
+    shown: Vec<Cell>,
+    // what colors are sent as
+    pub colors: ColorMode,
+    // the terminal's contents are unknown, clear it on the next present
+    wiped: bool,
+    // Wraps each frame in synchronized output escapes so it shows all at
+    // once.  Off by default, as some terminals print them.
+    pub sync: bool,
+}
+
+impl Color {
+    // "default", an ANSI color name, a 256 color index, or "#rrggbb"
+    pub fn parse(name: &str) -> Option<Color> {
+        let names = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+        let name = name.trim().to_lowercase();
+        if name == "default" {
+            return Some(Color::Default);
+        }
+        if let Some(hex) = name.strip_prefix('#') {
+            // from_str_radix alone would take a sign, as in "+1"
+            if hex.len() != 6 || !hex.bytes().all(|x| x.is_ascii_hexdigit()) {
+                return None;
+            }
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0);
+            return Some(Color::Rgb(channel(0), channel(2), channel(4)));
+        }
+        if let Some(i) = names.iter().position(|&x| x == name) {
+            return Some(Color::Ansi(i as u8));
+        }
+        name.parse().ok().map(Color::Ansi)
+    }
+    // the red, green and blue xterm shows it as, or None for the terminal's
+    // own default
+    pub fn rgb(&self) -> Option<(u8, u8, u8)> {
+        match *self {
+            Color::Default => None,
+            Color::Ansi(n) => Some(ansi_rgb(n)),
+            Color::Rgb(r, g, b) => Some((r, g, b)),
+        }
+    }
+    // 0-5 per channel of the 256 color cube, like termion's AnsiValue::rgb
+    pub fn cube(r: u8, g: u8, b: u8) -> Color {
+        Color::Ansi(16 + 36 * ::std::cmp::min(r, 5) + 6 * ::std::cmp::min(g, 5) +
+                    ::std::cmp::min(b, 5))
+    }
+    // the nearest color `mode` can show
+    pub fn degrade(&self, mode: ColorMode) -> Color {
+        match (*self, mode) {
+            (Color::Default, _) |
+            (_, ColorMode::TrueColor) |
+            (Color::Ansi(_), ColorMode::Ansi256) => *self,
+            (Color::Rgb(r, g, b), ColorMode::Ansi256) => Color::Ansi(nearest_256((r, g, b))),
+            (Color::Ansi(n), ColorMode::Ansi16) if n < 16 => *self,
+            (c, ColorMode::Ansi16) => Color::Ansi(nearest_16(c.rgb().unwrap_or((0, 0, 0)))),
+        }
+    }
+    // The SGR parameters to make it the foreground, or with `bg` the
+    // background.  The first 16 use their own codes, which 16 color
+    // terminals understand where they might not 38;5.
+    fn sgr(&self, bg: bool) -> String {
+        let base = if bg { 40 } else { 30 };
+        match *self {
+            Color::Default => format!("{}", base + 9),
+            Color::Ansi(n) if n < 8 => format!("{}", base + n as u16),
+            Color::Ansi(n) if n < 16 => format!("{}", base + 60 + (n - 8) as u16),
+            Color::Ansi(n) => format!("{};5;{}", base + 8, n),
+            Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+        }
+    }
+}
+
+impl ColorMode {
+    // Truecolor if COLORTERM says so, as terminals that have it set it to
+    // "truecolor" or "24bit".  Otherwise 256 colors, unless TERM is one of
+    // the old consoles that only have 16.
+    pub fn detect() -> ColorMode {
+        let colorterm = env::var("COLORTERM").unwrap_or_default().to_lowercase();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return ColorMode::TrueColor;
+        }
+        let term = env::var("TERM").unwrap_or_default();
+        if term == "linux" || term == "ansi" || term == "cons25" || term.starts_with("vt") {
+            ColorMode::Ansi16
+        } else {
+            ColorMode::Ansi256
+        }
+    }
+}
+
+// For config files and saved logs: a color as Color::parse takes it, a 256
+// color index, or 0-5 per channel of the color cube like [5, 5, 0].  Use with
+// #[serde(deserialize_with = "...")].
+pub fn deserialize_color<'de, D: Deserializer<'de>>(d: D) -> Result<Color, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Spec {
+        Index(u8),
+        Cube([u8; 3]),
+        Name(String),
+    }
+    match Spec::deserialize(d)? {
+        Spec::Index(n) => Ok(Color::Ansi(n)),
+        Spec::Cube([r, g, b]) if r < 6 && g < 6 && b < 6 => Ok(Color::cube(r, g, b)),
+        Spec::Cube(_) => Err(D::Error::custom("color cube channels go 0 to 5")),
+        Spec::Name(x) => {
+            Color::parse(&x).ok_or_else(|| D::Error::custom(format!("unknown color {:?}", x)))
+        }
+    }
+}
+
+// the other way: "default", an index, or "#rrggbb"
+pub fn serialize_color<S: Serializer>(color: &Color, s: S) -> Result<S::Ok, S::Error> {
+    match *color {
+        Color::Default => s.serialize_str("default"),
+        Color::Ansi(n) => s.serialize_u8(n),
+        Color::Rgb(r, g, b) => s.serialize_str(&format!("#{:02x}{:02x}{:02x}", r, g, b)),
+    }
+}
+
+// squared distance between two colors
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+// The closest of the color cube and the gray ramp.  0-15 are left out, as
+// terminals show them however they like.
+fn nearest_256(rgb: (u8, u8, u8)) -> u8 {
+    let level = |x: u8| {
+        (0..6).min_by_key(|&i| (CUBE_LEVELS[i] as i32 - x as i32).abs()).unwrap_or(0) as u8
+    };
+    let cube = 16 + 36 * level(rgb.0) + 6 * level(rgb.1) + level(rgb.2);
+    let avg = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let gray = 232 + ::std::cmp::min(avg.saturating_sub(3) / 10, 23) as u8;
+    if distance(ansi_rgb(gray), rgb) < distance(ansi_rgb(cube), rgb) {
+        gray
+    } else {
+        cube
+    }
+}
+
+fn nearest_16(rgb: (u8, u8, u8)) -> u8 {
+    (0..16).min_by_key(|&i| distance(ANSI_16[i], rgb)).unwrap_or(0) as u8
+}
+
+// xterm's first 16 colors
+const ANSI_16: [(u8, u8, u8); 16] = [(0x00, 0x00, 0x00), (0xcd, 0x00, 0x00), (0x00, 0xcd, 0x00),
+                                     (0xcd, 0xcd, 0x00), (0x00, 0x00, 0xee), (0xcd, 0x00, 0xcd),
+                                     (0x00, 0xcd, 0xcd), (0xe5, 0xe5, 0xe5), (0x7f, 0x7f, 0x7f),
+                                     (0xff, 0x00, 0x00), (0x00, 0xff, 0x00), (0xff, 0xff, 0x00),
+                                     (0x5c, 0x5c, 0xff), (0xff, 0x00, 0xff), (0x00, 0xff, 0xff),
+                                     (0xff, 0xff, 0xff)];
+// each step of the 6x6x6 color cube
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// 0-15 as above, then the color cube, then 24 grays
+fn ansi_rgb(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..=15 => ANSI_16[n as usize],
+        16..=231 => {
+            let i = n - 16;
+            (CUBE_LEVELS[(i / 36) as usize],
+             CUBE_LEVELS[(i / 6 % 6) as usize],
+             CUBE_LEVELS[(i % 6) as usize])
+        }
+        _ => {
+            let v = 8 + (n - 232) * 10;
+            (v, v, v)
+        }
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            text: " ".to_owned(),
+            style: Style::default(),
+        }
+    }
+}
+
+impl Style {
+    // Applies the parameters of an SGR sequence (ESC [ ... m), the subset
+    // termion and Message write.
+    pub fn apply_sgr(&mut self, params: &str) {
+        let nums: Vec<u16> = params.split(';').map(|x| x.parse().unwrap_or(0)).collect();
+        let mut i = 0;
+        while i < nums.len() {
+            match nums[i] {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.invert = true,
+                22 => {
+                    self.bold = false;
+                    self.dim = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.invert = false,
+                n @ 30..=37 => self.fg = Color::Ansi((n - 30) as u8),
+                n @ 90..=97 => self.fg = Color::Ansi((n - 90 + 8) as u8),
+                39 => self.fg = Color::Default,
+                n @ 40..=47 => self.bg = Color::Ansi((n - 40) as u8),
+                n @ 100..=107 => self.bg = Color::Ansi((n - 100 + 8) as u8),
+                49 => self.bg = Color::Default,
+                n @ 38 | n @ 48 => {
+                    // 38;5;N or 38;2;R;G;B
+                    let c = match nums.get(i + 1) {
+                        Some(&5) => {
+                            let c = nums.get(i + 2).map(|&v| Color::Ansi(v as u8));
+                            i += 2;
+                            c
+                        }
+                        Some(&2) => {
+                            let c = nums.get(i + 2..i + 5)
+                                .map(|x| Color::Rgb(x[0] as u8, x[1] as u8, x[2] as u8));
+                            i += 4;
+                            c
+                        }
+                        _ => None,
+                    };
+                    if let Some(c) = c {
+                        if n == 38 {
+                            self.fg = c;
+                        } else {
+                            self.bg = c;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    // with its colors as `mode` can show them
+    pub fn degrade(&self, mode: ColorMode) -> Style {
+        Style {
+            fg: self.fg.degrade(mode),
+            bg: self.bg.degrade(mode),
+            ..*self
+        }
+    }
+
+    // the shortest SGR sequence that turns this style into `to`
+    pub fn sgr_to(&self, to: &Style) -> String {
+        let mut codes: Vec<String> = Vec::new();
+        // bold and dim share an off switch
+        if (self.bold && !to.bold) || (self.dim && !to.dim) {
+            codes.push("22".to_owned());
+            if to.bold {
+                codes.push("1".to_owned());
+            }
+            if to.dim {
+                codes.push("2".to_owned());
+            }
+        } else {
+            if to.bold && !self.bold {
+                codes.push("1".to_owned());
+            }
+            if to.dim && !self.dim {
+                codes.push("2".to_owned());
+            }
+        }
+        let flags = [(self.italic, to.italic, "3", "23"),
+                     (self.underline, to.underline, "4", "24"),
+                     (self.invert, to.invert, "7", "27")];
+        for &(from, to, on, off) in flags.iter() {
+            if from != to {
+                codes.push(if to { on } else { off }.to_owned());
+            }
+        }
+        if self.fg != to.fg {
+            codes.push(to.fg.sgr(false));
+        }
+        if self.bg != to.bg {
+            codes.push(to.bg.sgr(true));
+        }
+        if codes.is_empty() {
+            String::new()
+        } else {
+            format!("\x1b[{}m", codes.join(";"))
+        }
+    }
+}
+
+impl Glyph {
+    pub fn new(text: &str, style: Style) -> Self {
+        Glyph {
+            text: text.to_owned(),
+            style,
+            offset: (0, 0),
+        }
+    }
+}
+
+// A line of glyphs as text with SGR escapes, ending plain.  Offsets can't be
+// shown this way and are dropped.
+pub fn sgr_line(glyphs: &[Glyph]) -> String {
+    let mut line = String::new();
+    let mut pen = Style::default();
+    for g in glyphs {
+        line.push_str(&pen.sgr_to(&g.style));
+        line.push_str(&g.text);
+        pen = g.style;
+    }
+    line.push_str(&pen.sgr_to(&Style::default()));
+    line
+}
+
+impl Screen {
+    pub fn new(width: u16, height: u16) -> Self {
+        let size = width as usize * height as usize;
+        Screen {
+            width,
+            height,
+            cells: vec![Cell::default(); size],
+            shown: vec![Cell::default(); size],
+            wiped: true,
+            sync: false,
+            colors: ColorMode::detect(),
+        }
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    // starts over at a new size; the next present redraws everything
+    pub fn resize(&mut self, width: u16, height: u16) {
+        if (width, height) != self.size() {
+            *self = Screen {
+                sync: self.sync,
+                colors: self.colors,
+                ..Screen::new(width, height)
+            };
+        }
+    }
+
+    // the terminal has been scribbled on, e.g. by a resize
+    pub fn forget(&mut self) {
+        self.wiped = true;
+    }
+
+    // blanks the frame being drawn
+    pub fn clear(&mut self) {
+        for c in self.cells.iter_mut() {
+            *c = Cell::default();
+        }
+    }
+
+    // Draws `text`, which may contain SGR escapes, starting at column x of
+    // row y.  Coordinates are 1-based like cursor::Goto.  The style starts
+    // out plain, and anything past the right edge is cut off.
+    pub fn print(&mut self, x: u16, y: u16, text: &str) {
+        let width = self.width;
+        self.print_clipped(x, y, width, text)
+    }
+
+    // print, but cut off after `width` columns
+    pub fn print_clipped(&mut self, x: u16, y: u16, width: u16, text: &str) {
+        if y == 0 || y > self.height || x == 0 {
+            return;
+        }
+        let edge = ::std::cmp::min(self.width as usize, (x - 1) as usize + width as usize);
+        let row = (y - 1) as usize * self.width as usize;
+        let mut col = (x - 1) as usize;
+        let mut style = Style::default();
+        let mut rest = text;
+        while !rest.is_empty() {
+            let plain = rest.find('\x1b').unwrap_or(rest.len());
+            for g in rest[..plain].graphemes(true) {
+                let w = str_width(g);
+                if w == 0 {
+                    // a control character, or a mark with nothing to sit on
+                    continue;
+                }
+                if col + w > edge {
+                    return;
+                }
+                self.put(row, col, g, w, style);
+                col += w;
+            }
+            rest = &rest[plain..];
+            if rest.is_empty() {
+                break;
+            }
+            // ESC [ params final
+            let end = rest.char_indices()
+                .skip(2)
+                .find(|&(_, c)| ('@'..='~').contains(&c))
+                .map(|(i, _)| i);
+            match end {
+                Some(end) => {
+                    if rest.starts_with("\x1b[") && rest[end..].starts_with('m') {
+                        style.apply_sgr(&rest[2..end]);
+                    }
+                    rest = &rest[end + 1..];
+                }
+                None => break,
+            }
+        }
+    }
+
+    // Draws glyphs left to right from column x of row y, each moved by its
+    // offset but kept within `clip`, so they can't stray onto other panes.
+    // Moved glyphs go on top so their neighbours don't cover them, and any
+    // that would land off screen are left out.
+    pub fn print_glyphs(&mut self, x: u16, y: u16, clip: Rect, glyphs: &[Glyph]) {
+        let keep = |at: i32, low: u16, high: i32| {
+            ::std::cmp::max(::std::cmp::min(at, high), low as i32)
+        };
+        for &moved in [false, true].iter() {
+            let mut col = x as i32;
+            for g in glyphs {
+                let w = str_width(&g.text) as i32;
+                let (mut cx, mut cy) = (col, y as i32);
+                col += w;
+                if w == 0 || (g.offset != (0, 0)) != moved {
+                    continue;
+                }
+                if moved {
+                    let right = clip.x as i32 + clip.width as i32 - w;
+                    cx = keep(cx + g.offset.0 as i32, clip.x, right);
+                    cy = keep(cy + g.offset.1 as i32, clip.y, clip.bottom() as i32);
+                }
+                // 0-based from here on
+                cx -= 1;
+                cy -= 1;
+                if cx < 0 || cy < 0 || cx + w > self.width as i32 || cy >= self.height as i32 {
+                    continue;
+                }
+                let row = cy as usize * self.width as usize;
+                self.put(row, cx as usize, &g.text, w as usize, g.style);
+            }
+        }
+    }
+
+    // the cell at column x of row y, 1-based
+    pub fn cell(&self, x: u16, y: u16) -> Option<&Cell> {
+        if x == 0 || y == 0 || x > self.width || y > self.height {
+            return None;
+        }
+        self.cells.get((y - 1) as usize * self.width as usize + (x - 1) as usize)
+    }
+
+    // the text of row y, without styles
+    pub fn row_text(&self, y: u16) -> String {
+        (1..self.width + 1).filter_map(|x| self.cell(x, y)).map(|c| c.text.as_str()).collect()
+    }
+
+    // Puts one grapheme at column x of row y, as print would, and returns
+    // how many columns it took.  Zero width graphemes join the one before.
+    pub fn put_grapheme(&mut self, x: u16, y: u16, g: &str, style: Style) -> usize {
+        if x == 0 || y == 0 || x > self.width || y > self.height {
+            return 0;
+        }
+        let row = (y - 1) as usize * self.width as usize;
+        let col = (x - 1) as usize;
+        let w = str_width(g);
+        if w == 0 {
+            if col > 0 {
+                self.cells[row + col - 1].text.push_str(g);
+            }
+            return 0;
+        }
+        if col + w > self.width as usize {
+            return 0;
+        }
+        self.put(row, col, g, w, style);
+        w
+    }
+
+    // blanks columns `from` to the end of row y
+    pub fn clear_row(&mut self, from: u16, y: u16) {
+        for x in ::std::cmp::max(from, 1)..self.width + 1 {
+            self.put_grapheme(x, y, " ", Style::default());
+        }
+    }
+
+    fn put(&mut self, row: usize, col: usize, g: &str, w: usize, style: Style) {
+        let width = self.width as usize;
+        // don't leave half of a wide grapheme behind
+        if self.cells[row + col].text.is_empty() && col > 0 {
+            self.cells[row + col - 1].text = " ".to_owned();
+        }
+        let next = col + w;
+        if next < width && self.cells[row + next].text.is_empty() {
+            self.cells[row + next].text = " ".to_owned();
+        }
+        self.cells[row + col] = Cell {
+            text: g.to_owned(),
+            style,
+        };
+        for c in self.cells[row + col + 1..row + next].iter_mut() {
+            *c = Cell {
+                text: String::new(),
+                style,
+            };
+        }
+    }
+
+    // Writes out whatever changed since the last present, moving the
+    // cursor and switching styles only where needed.
+    pub fn present<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        let mut buf = String::new();
+        if self.wiped {
+            buf.push_str(SGR_RESET);
+            buf.push_str(clear::All.as_ref());
+            for c in self.shown.iter_mut() {
+                *c = Cell::default();
+            }
+            self.wiped = false;
+        }
+        let width = self.width as usize;
+        let mut pen: Option<Style> = None;
+        let mut at = None;
+        for y in 0..self.height as usize {
+            for x in 0..width {
+                let i = y * width + x;
+                if self.cells[i] == self.shown[i] {
+                    continue;
+                }
+                // a changed right half means redrawing its left half
+                let x = if self.cells[i].text.is_empty() && x > 0 {
+                    x - 1
+                } else {
+                    x
+                };
+                let i = y * width + x;
+                if at != Some((x, y)) {
+                    buf.push_str(&cursor::Goto(x as u16 + 1, y as u16 + 1).to_string());
+                }
+                let style = self.cells[i].style.degrade(self.colors);
+                match pen {
+                    Some(ref p) => buf.push_str(&p.sgr_to(&style)),
+                    None => {
+                        buf.push_str(SGR_RESET);
+                        buf.push_str(&Style::default().sgr_to(&style));
+                    }
+                }
+                pen = Some(style);
+                let w = ::std::cmp::max(str_width(&self.cells[i].text), 1);
+                if self.cells[i].text.is_empty() {
+                    buf.push(' ');
+                } else {
+                    buf.push_str(&self.cells[i].text);
+                }
+                for k in i..::std::cmp::min(i + w, y * width + width) {
+                    self.shown[k] = self.cells[k].clone();
+                }
+                at = Some((x + w, y));
+            }
+        }
+        if buf.is_empty() {
+            return Ok(());
+        }
+        if pen.is_some() {
+            buf.push_str(SGR_RESET);
+        }
+        if self.sync {
+            buf = format!("{}{}{}", SYNC_BEGIN, buf, SYNC_END);
+        }
+        out.write_all(buf.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{nearest_16, nearest_256, Color, Glyph, Screen, Style, SYNC_BEGIN, SYNC_END};
+    use term::Rect;
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(Color::parse("#ffd700"), Some(Color::Rgb(0xff, 0xd7, 0x00)));
+        assert_eq!(Color::parse(" #FFD700 "), Some(Color::Rgb(0xff, 0xd7, 0x00)));
+        assert_eq!(Color::parse("#ffd70"), None);
+        assert_eq!(Color::parse("#ffd7000"), None);
+        assert_eq!(Color::parse("#+1+1+1"), None);
+        assert_eq!(Color::parse("#ffd7zz"), None);
+        // six bytes, but not six digits
+        assert_eq!(Color::parse("#ééé"), None);
+        assert_eq!(Color::parse("#a€bc"), None);
+    }
+
+    #[test]
+    fn nearest_256_colors() {
+        assert_eq!(nearest_256((0, 0, 0)), 16);
+        assert_eq!(nearest_256((255, 255, 255)), 231);
+        assert_eq!(nearest_256((0xff, 0xd7, 0x00)), 220);
+        assert_eq!(nearest_256((95, 135, 175)), 67);
+        // grays go on the ramp
+        assert_eq!(nearest_256((128, 128, 128)), 244);
+        assert_eq!(nearest_256((18, 18, 18)), 233);
+    }
+
+    #[test]
+    fn nearest_16_colors() {
+        assert_eq!(nearest_16((0, 0, 0)), 0);
+        assert_eq!(nearest_16((255, 255, 255)), 15);
+        assert_eq!(nearest_16((200, 10, 10)), 1);
+        assert_eq!(nearest_16((250, 20, 20)), 9);
+        assert_eq!(nearest_16((128, 128, 128)), 8);
+        assert_eq!(nearest_16((0x5f, 0x5f, 0xff)), 12);
+    }
+
+    #[test]
+    fn sgr_uses_16_color_codes() {
+        assert_eq!(Color::Ansi(1).sgr(false), "31");
+        assert_eq!(Color::Ansi(7).sgr(true), "47");
+        assert_eq!(Color::Ansi(9).sgr(false), "91");
+        assert_eq!(Color::Ansi(15).sgr(true), "107");
+        assert_eq!(Color::Ansi(16).sgr(false), "38;5;16");
+        assert_eq!(Color::Rgb(1, 2, 3).sgr(true), "48;2;1;2;3");
+        assert_eq!(Color::Default.sgr(false), "39");
+        // and they read back the same
+        for n in 0..16 {
+            let mut style = Style::default();
+            style.apply_sgr(&Color::Ansi(n).sgr(false));
+            style.apply_sgr(&Color::Ansi(n).sgr(true));
+            assert_eq!((style.fg, style.bg), (Color::Ansi(n), Color::Ansi(n)));
+        }
+    }
+
+    #[test]
+    fn moved_glyphs_stay_in_their_rows() {
+        let mut screen = Screen::new(5, 4);
+        screen.clear();
+        let mut glyphs: Vec<Glyph> = "abc".chars()
+            .map(|c| Glyph::new(&c.to_string(), Style::default()))
+            .collect();
+        glyphs[0].offset = (0, -1);
+        glyphs[1].offset = (0, 1);
+        glyphs[2].offset = (0, 5);
+        // rows 2 and 3 only
+        let clip = Rect { x: 1, y: 2, width: 5, height: 2 };
+        screen.print_glyphs(1, 2, clip, &glyphs);
+        assert_eq!(screen.row_text(1), "     ");
+        assert_eq!(screen.row_text(2), "a    ");
+        assert_eq!(screen.row_text(3), " bc  ");
+        assert_eq!(screen.row_text(4), "     ");
+    }
+
+    #[test]
+    fn moved_glyphs_stay_in_their_columns() {
+        let mut screen = Screen::new(8, 1);
+        screen.clear();
+        let mut glyphs: Vec<Glyph> = "abcd".chars()
+            .map(|c| Glyph::new(&c.to_string(), Style::default()))
+            .collect();
+        glyphs[0].offset = (-1, 0);
+        glyphs[3].offset = (2, 0);
+        // columns 3 to 6, say beside a portrait
+        let clip = Rect { x: 3, y: 1, width: 4, height: 1 };
+        screen.print_glyphs(3, 1, clip, &glyphs);
+        assert_eq!(screen.row_text(1), "  abcd  ");
+    }
+
+    #[test]
+    fn syncs_frames_only_when_asked() {
+        let mut screen = Screen::new(4, 1);
+        let frame = |screen: &mut Screen, text: &str| {
+            let mut out = Vec::new();
+            screen.clear();
+            screen.print_clipped(1, 1, 4, text);
+            screen.present(&mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let plain = frame(&mut screen, "ab");
+        assert!(plain.contains("ab") && !plain.contains("?2026"));
+        screen.sync = true;
+        let synced = frame(&mut screen, "cd");
+        assert!(synced.starts_with(SYNC_BEGIN) && synced.ends_with(SYNC_END));
+        // nothing to send, so nothing at all
+        assert_eq!(frame(&mut screen, "cd"), "");
+    }
+}
@@ -0,0 +1,246 @@
+use super::{Message, Theme};
+
+// Where the log view is, plus the log's formatted lines so they aren't
+// rewrapped every frame.
+#[derive(Default)]
+pub struct Scrollback {
+    // lines scrolled back from the bottom, 0 follows new messages
+    pub offset: usize,
+    // log length when the view was last at the bottom
+    seen: usize,
+    width: usize,
+    lines: Vec<Vec<String>>,
+    // (message, line) at the bottom of a scrolled back view when the lines
+    // were dropped, to scroll back to once they're formatted again
+    anchor: Option<(usize, usize)>,
+}
+
+impl Scrollback {
+    pub fn new() -> Self {
+        Scrollback::default()
+    }
+
+    // Formats anything new in the log.  While scrolled back, the offset grows
+    // by the new lines so the view stays put.
+    pub fn update(&mut self, log: &[Message], width: usize, theme: &Theme) {
+        if width != self.width || log.len() < self.lines.len() {
+            self.invalidate();
+            self.width = width;
+        }
+        let old = self.lines.len();
+        for msg in &log[old..] {
+            let lines = msg.format_log(width, theme);
+            if self.offset > 0 && old > 0 {
+                self.offset = self.offset.saturating_add(lines.len());
+            }
+            self.lines.push(lines);
+        }
+        if let Some((m, l)) = self.anchor.take() {
+            self.offset = match self.lines.get(m) {
+                // the same line if it's still there after rewrapping, or
+                // the message's last
+                Some(x) => {
+                    let at: usize = self.lines[..m].iter().map(|x| x.len()).sum();
+                    let l = ::std::cmp::min(l, x.len().saturating_sub(1));
+                    self.total().saturating_sub(at + l + 1)
+                }
+                None => 0,
+            };
+        }
+        if self.offset == 0 || self.seen > log.len() {
+            self.seen = log.len();
+        }
+    }
+
+    // Drops the formatted lines, e.g. when the width or theme changes.  A
+    // scrolled back view stays on the same message.
+    pub fn invalidate(&mut self) {
+        if self.offset > 0 && self.anchor.is_none() {
+            let total = self.total();
+            let end = total.saturating_sub(self.offset);
+            self.anchor = self.line_at(end.saturating_sub(1));
+        }
+        self.lines.clear();
+    }
+
+    // the (message, line) of line `n` from the top
+    fn line_at(&self, mut n: usize) -> Option<(usize, usize)> {
+        for (m, x) in self.lines.iter().enumerate() {
+            if n < x.len() {
+                return Some((m, n));
+            }
+            n -= x.len();
+        }
+        None
+    }
+
+    // the width the cached lines were wrapped to
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn total(&self) -> usize {
+        self.lines.iter().map(|x| x.len()).sum()
+    }
+
+    // the lines that fit in `height` rows at the current offset, top first
+    pub fn visible(&mut self, height: usize) -> Vec<&str> {
+        let shown = self.visible_at(height);
+        let lines = &self.lines;
+        shown.into_iter().map(|(m, l)| lines[m][l].as_str()).collect()
+    }
+
+    // visible() as (message, line of that message) pairs
+    pub fn visible_at(&mut self, height: usize) -> Vec<(usize, usize)> {
+        let total = self.total();
+        let max = total.saturating_sub(height);
+        if self.offset > max {
+            self.offset = max;
+        }
+        let end = total - self.offset;
+        let start = end.saturating_sub(height);
+        self.lines
+            .iter()
+            .enumerate()
+            .flat_map(|(m, x)| (0..x.len()).map(move |l| (m, l)))
+            .skip(start)
+            .take(end - start)
+            .collect()
+    }
+
+    // the cached lines of message `m`
+    pub fn lines(&self, m: usize) -> &[String] {
+        &self.lines[m]
+    }
+
+    // messages that arrived while scrolled back
+    pub fn unseen(&self, log_len: usize) -> usize {
+        log_len.saturating_sub(self.seen)
+    }
+    pub fn up(&mut self, lines: usize) {
+        // visible() clamps this to the top
+        self.offset = self.offset.saturating_add(lines);
+    }
+    pub fn down(&mut self, lines: usize) {
+        self.offset = self.offset.saturating_sub(lines);
+    }
+    pub fn top(&mut self) {
+        self.offset = usize::MAX;
+    }
+    pub fn bottom(&mut self) {
+        self.offset = 0;
+    }
+    pub fn at_bottom(&self) -> bool {
+        self.offset == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use termion::event::{Key, MouseButton, MouseEvent};
+
+    use term::{Color, Input, Message, Name, Term, Theme, VirtualTerminal};
+
+    fn said(text: &str) -> Message {
+        Message {
+            name: Name::Other("Robot".to_owned()),
+            speaker: None,
+            markup: None,
+            time: SystemTime::now(),
+            color: Color::Default,
+            buffer: text.to_owned(),
+            spans: Vec::new(),
+            reveals: Vec::new(),
+            frozen: true,
+        }
+    }
+
+    // 5 log rows 20 columns wide, holding messages 1 to 20
+    fn term() -> Term<VirtualTerminal> {
+        let mut term = Term::with_backend(VirtualTerminal::new(30, 10)).unwrap();
+        for i in 1..21 {
+            term.log.push(said(&format!("message {}", i)));
+        }
+        term.draw().unwrap();
+        term
+    }
+
+    // the last log row, and the status bar's note
+    fn shown(term: &mut Term<VirtualTerminal>) -> (String, String) {
+        term.draw().unwrap();
+        let rows = term.backend().rows();
+        (rows[4].trim().to_owned(), rows[7].trim().to_owned())
+    }
+
+    fn wheel(button: MouseButton) -> Input {
+        Input::Mouse(MouseEvent::Press(button, 1, 1))
+    }
+
+    #[test]
+    fn pages_and_wheel() {
+        let mut term = term();
+        assert_eq!(shown(&mut term).0, "<Robot> message 20");
+        term.handle_key(Key::PageUp);
+        assert_eq!(shown(&mut term).0, "<Robot> message 16");
+        term.handle_input(wheel(MouseButton::WheelUp));
+        assert_eq!(shown(&mut term).0, "<Robot> message 13");
+        term.handle_input(wheel(MouseButton::WheelDown));
+        term.handle_key(Key::PageDown);
+        assert_eq!(shown(&mut term).0, "<Robot> message 20");
+        // Home on an empty input line goes to the top, and no further
+        term.handle_key(Key::Home);
+        term.handle_key(Key::PageUp);
+        assert_eq!(shown(&mut term).0, "<Robot> message 5");
+        term.handle_key(Key::End);
+        assert!(term.scrollback.at_bottom());
+    }
+
+    #[test]
+    fn notes_new_messages_below() {
+        let mut term = term();
+        assert_eq!(shown(&mut term).1, "");
+        term.handle_key(Key::PageUp);
+        assert_eq!(shown(&mut term), ("<Robot> message 16".to_owned(), "more below".to_owned()));
+        term.log.push(said("new"));
+        assert_eq!(shown(&mut term).1, "1 new message");
+        term.log.push(said("newer"));
+        // the view stays put as they come in
+        let (last, note) = shown(&mut term);
+        assert_eq!((last.as_str(), note.as_str()), ("<Robot> message 16", "2 new messages"));
+        term.handle_key(Key::End);
+        assert_eq!(shown(&mut term), ("<Robot> newer".to_owned(), "".to_owned()));
+        term.handle_key(Key::PageUp);
+        assert_eq!(shown(&mut term).1, "more below");
+    }
+
+    #[test]
+    fn rewrapping_keeps_the_place() {
+        let mut term = term();
+        term.handle_key(Key::PageUp);
+        assert_eq!(shown(&mut term).0, "<Robot> message 16");
+        term.backend_mut().resize(40, 10);
+        term.resize();
+        assert_eq!(shown(&mut term).0, "<Robot> message 16");
+        term.set_theme(Theme::light());
+        assert_eq!(shown(&mut term).0, "<Robot> message 16");
+        // narrow enough to wrap each onto two lines: the bottom is still
+        // message 16's first
+        term.backend_mut().resize(24, 10);
+        term.resize();
+        assert_eq!(shown(&mut term).0, "<Robot>");
+        assert_eq!(term.backend().rows()[3].trim(), "message 15");
+    }
+
+    #[test]
+    fn restoring_a_shorter_log() {
+        let mut term = term();
+        term.handle_key(Key::PageUp);
+        term.log.push(said("new"));
+        shown(&mut term);
+        let records: Vec<_> = term.records()[..3].to_vec();
+        term.restore_log(records);
+        assert_eq!(shown(&mut term), ("<Robot> message 3".to_owned(), "".to_owned()));
+    }
+}
@@ -0,0 +1,211 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use toml;
+
+use super::screen::{self, Color, Style};
+
+// How Term's own parts look, as opposed to what speakers say.  Loaded from a
+// TOML file like the one below; anything left out is as in Theme::dark().
+//
+//     border_bg = "#303030"
+//     border_fill = "─"
+//     status_open = "┤ "
+//     status_close = " ├"
+//     name_open = "["
+//     name_close = "]"
+//     player_color = "#5f87ff"
+//     log_margin = [2, 2]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    // the status bar: its colors, and what fills it around the notes
+    #[serde(deserialize_with = "screen::deserialize_color",
+            serialize_with = "screen::serialize_color")]
+    pub border_fg: Color,
+    #[serde(deserialize_with = "screen::deserialize_color",
+            serialize_with = "screen::serialize_color")]
+    pub border_bg: Color,
+    pub border_fill: char,
+    // either side of the status and the scrollback note
+    pub status_open: String,
+    pub status_close: String,
+    // either side of a speaker's name, as in "<Name>"
+    pub name_open: String,
+    pub name_close: String,
+    #[serde(deserialize_with = "screen::deserialize_color",
+            serialize_with = "screen::serialize_color")]
+    pub player_color: Color,
+    // for anything that isn't someone's words, like the name brackets
+    #[serde(deserialize_with = "screen::deserialize_color",
+            serialize_with = "screen::serialize_color")]
+    pub text: Color,
+    // The terminal background the theme is made for.  Exports use it and
+    // `text` for Color::Default.
+    #[serde(deserialize_with = "screen::deserialize_color",
+            serialize_with = "screen::serialize_color")]
+    pub background: Color,
+    // blank columns (left, right) around Pane::Log
+    pub log_margin: (u16, u16),
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    // for light on dark terminals, the way Term has always looked
+    pub fn dark() -> Self {
+        Theme {
+            border_fg: Color::Default,
+            border_bg: Color::cube(1, 1, 1),
+            border_fill: ' ',
+            status_open: " ".to_owned(),
+            status_close: " ".to_owned(),
+            name_open: "<".to_owned(),
+            name_close: ">".to_owned(),
+            player_color: Color::cube(0, 0, 5),
+            text: Color::Default,
+            background: Color::Default,
+            log_margin: (4, 6),
+        }
+    }
+
+    // for dark on light terminals
+    pub fn light() -> Self {
+        Theme {
+            border_fg: Color::Ansi(0),
+            border_bg: Color::cube(4, 4, 4),
+            player_color: Color::cube(0, 0, 3),
+            text: Color::Ansi(0),
+            background: Color::Ansi(15),
+            ..Theme::dark()
+        }
+    }
+
+    // as far apart as colors go, with square brackets that are easier to
+    // tell from the text than angle ones
+    pub fn high_contrast() -> Self {
+        Theme {
+            border_fg: Color::Ansi(0),
+            border_bg: Color::Ansi(15),
+            name_open: "[".to_owned(),
+            name_close: "]".to_owned(),
+            player_color: Color::Ansi(14),
+            text: Color::Ansi(15),
+            background: Color::Ansi(0),
+            ..Theme::dark()
+        }
+    }
+
+    // "dark", "light" or "high-contrast"
+    pub fn builtin(name: &str) -> Option<Theme> {
+        match name {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            "high-contrast" => Some(Theme::high_contrast()),
+            _ => None,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<Error>> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        Theme::parse(&text)
+    }
+    pub fn parse(text: &str) -> Result<Self, Box<Error>> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn text_style(&self) -> Style {
+        Style { fg: self.text, ..Style::default() }
+    }
+    pub fn border_style(&self) -> Style {
+        Style {
+            fg: self.border_fg,
+            bg: self.border_bg,
+            ..Style::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use toml;
+
+    use super::Theme;
+    use term::{Color, Term, VirtualTerminal};
+
+    #[test]
+    fn parses_what_it_says_and_defaults_the_rest() {
+        let theme = Theme::parse(r##"
+            border_fg = "red"
+            border_bg = "#303030"
+            border_fill = "─"
+            status_open = "┤ "
+            player_color = [0, 2, 5]
+            text = 7
+            background = "default"
+            log_margin = [2, 3]
+        "##)
+            .unwrap();
+        assert_eq!(theme,
+                   Theme {
+                       border_fg: Color::Ansi(1),
+                       border_bg: Color::Rgb(0x30, 0x30, 0x30),
+                       border_fill: '─',
+                       status_open: "┤ ".to_owned(),
+                       player_color: Color::cube(0, 2, 5),
+                       text: Color::Ansi(7),
+                       background: Color::Default,
+                       log_margin: (2, 3),
+                       ..Theme::dark()
+                   });
+        assert_eq!(Theme::parse("").unwrap(), Theme::dark());
+    }
+
+    #[test]
+    fn bad_themes_are_errors() {
+        for text in &["border_bg = \"mauve\"",
+                      "border_bg = \"#12345\"",
+                      "player_color = [0, 6, 0]",
+                      "text = 256",
+                      "border_fill = \"ab\"",
+                      // a typo shouldn't quietly do nothing
+                      "name_opne = \"[\""] {
+            assert!(Theme::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn builtins_round_trip() {
+        for name in &["dark", "light", "high-contrast"] {
+            let theme = Theme::builtin(name).unwrap();
+            let text = toml::to_string(&theme).unwrap();
+            assert_eq!(Theme::parse(&text).unwrap(), theme);
+        }
+        assert_eq!(Theme::builtin("solarized"), None);
+    }
+
+    #[test]
+    fn status_bar_uses_the_border_characters() {
+        let mut term = Term::with_backend(VirtualTerminal::new(20, 6)).unwrap();
+        term.set_theme(Theme {
+            border_fill: '═',
+            status_open: "[".to_owned(),
+            status_close: "]".to_owned(),
+            ..Theme::dark()
+        });
+        term.status = "世界".to_owned();
+        term.draw().unwrap();
+        // the wide status takes up its columns, and the fill makes up the rest
+        assert_eq!(term.backend().rows()[3], "[世界]══════════════");
+        term.set_theme(Theme { border_fill: '世', ..Theme::dark() });
+        term.draw().unwrap();
+        assert_eq!(term.backend().rows()[3], " 世界 世世世世世世世");
+    }
+}
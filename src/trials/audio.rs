@@ -2,7 +2,7 @@ use portaudio;
 
 use std::io::{self, Write};
 
-use audio::{wave, Mixer};
+use audio::{wave, Mixer, Voice};
 use super::sleep;
 
 pub fn cb_mock() -> Result<(), portaudio::Error> {
@@ -118,19 +118,21 @@ pub fn wave_mock() -> Result<(), portaudio::Error> {
 pub fn beep_mock() -> Result<(), portaudio::Error> {
     let mut mix = Mixer::new()?;
 
-    let char_speed = 100;
+    let voice = Voice::default();
+    let char_speed = voice.char_millis();
     let text = "Well, I think this whole thing needs lots more testing!";
-    // let text = "Hmm...        HMMMMMMMMMM!        ";
-    let wv = speech_wave(text, char_speed);
-    let backwards_wv = speech_wave(text, char_speed).rev();
+    // let text = "Hmm... HMMMMMMMMMM!";
+    let wv = voice.speak(text);
+    let backwards_wv = voice.speak(text).rev();
+    let reveal = voice.reveal(text);
 
     mix.new_stream(wv)?;
     try!(mix.start());
 
-    for c in text.chars() {
+    for &(ref c, millis) in reveal.iter() {
+        sleep(millis as u64);
         print!("{}", c);
         io::stdout().flush().unwrap();
-        sleep(char_speed as u64);
     }
     sleep(char_speed as u64);
     try!(mix.stop());
@@ -138,10 +140,10 @@ pub fn beep_mock() -> Result<(), portaudio::Error> {
     println!("\nForward Test finished.");
     mix.new_stream(backwards_wv)?;
     try!(mix.start());
-    for c in text.chars().rev() {
+    for &(ref c, millis) in reveal.iter().rev() {
         print!("{}", c);
         io::stdout().flush().unwrap();
-        sleep(char_speed as u64);
+        sleep(millis as u64);
     }
     sleep(char_speed as u64);
     try!(mix.stop());
@@ -149,20 +151,24 @@ pub fn beep_mock() -> Result<(), portaudio::Error> {
     Ok(())
 }
 
-pub fn speech_wave(text: &str, char_speed: usize) -> Box<DoubleEndedIterator<Item = f32>> {
-    let mut pitches: Vec<usize> = Vec::new();
-    for (i, c) in text.chars().enumerate() {
-        if c.is_whitespace() {
-            pitches.push(0);
-            continue;
-        }
-        let p = match i % 4 {
-            0 => 125,
-            1 => 150,
-            3 => 200,
-            _ => 100,
-        };
-        pitches.push(p);
+pub fn animalese_mock() -> Result<(), portaudio::Error> {
+    let mut mix = Mixer::new()?;
+
+    let voice = Voice::animalese();
+    let text = "Hello there!  Have you been testing the new voices?";
+    let reveal = voice.reveal(text);
+
+    mix.new_stream(voice.speak(text))?;
+    try!(mix.start());
+    for &(ref c, millis) in reveal.iter() {
+        sleep(millis as u64);
+        print!("{}", c);
+        io::stdout().flush().unwrap();
+    }
+    while mix.is_active().unwrap() {
+        sleep(10);
     }
-    wave::bookend(wave::multi_wave(&pitches, char_speed), char_speed / 4)
+    try!(mix.close());
+    println!("\nAnimalese Test finished.");
+    Ok(())
 }
@@ -2,7 +2,7 @@ mod audio;
 mod term;
 
 pub use self::audio::*;
-pub use self::term::term_mock;
+pub use self::term::{term_mock, speech_mock, input_mock, effects_mock};
 
 use std::thread;
 use std::time;
@@ -1,50 +1,44 @@
-use termion;
+use termion::event::Key;
 
-use term::{Term, Message, Name};
+use term::{Term, Input, Inputs, Layout};
+use audio::Mixer;
+use speaker::Registry;
 use super::sleep;
 use std::error::Error;
 
 pub fn term_mock() -> Result<(), Box<Error>> {
+    let speakers = Registry::load("data/speakers.toml")?;
+    let robot = speakers.get("robot").ok_or("no robot speaker")?;
     let mut term = Term::init()?;
     term.user_name = "Player".to_owned();
-    term.user_buffer.push('x');
-    let msg = Message {
-        name: Name::Other("Testing Robot"),
-        color: termion::color::AnsiValue::rgb(5, 5, 0),
-        buffer: Vec::new(),
-    };
-    term.msg_buffer = Some(msg.clone());
-    let text = "Hello, this is the beginning of the test.";
-    let char_speed = 100;
+    term.handle_key(Key::Char('x'));
+    let mut msg = robot.message();
+    let text = msg.set_markup("Hello, this is {speed 0.5}{b}the beginning{/b}{/speed} of \
+                               the {color yellow}{u}test{/u}{/color}.{pause 400}");
+    term.msg_buffer = Some(msg);
     let mut flip = false;
-    for c in text.chars() {
-        // for c in "HELLO TESTING WORLD!".chars().cycle().take(100) {
-        term.msg_buffer.as_mut().unwrap().buffer.push(c);
+    for (c, millis) in robot.voice.reveal_marked(&text, &speakers.voices()) {
+        sleep(millis as u64);
+        term.msg_buffer.as_mut().unwrap().buffer.push_str(&c);
         if flip {
-            term.user_buffer.push(' ');
-            term.user_buffer.push('x');
+            term.handle_key(Key::Char(' '));
+            term.handle_key(Key::Char('x'));
         } else {
-            term.user_buffer.push('_');
-            term.user_buffer.push('X');
+            term.handle_key(Key::Char('_'));
+            term.handle_key(Key::Char('X'));
         }
-        sleep(char_speed as u64);
         term.draw()?;
         flip = !flip;
     }
     sleep(50);
     term.msg_done();
-    term.user_msg_done();
-    term.user_buffer.push('!');
+    term.handle_key(Key::Char('\n'));
+    term.handle_key(Key::Char('!'));
     term.draw()?;
     sleep(1000);
 
-    let msg = Message {
-        name: Name::Other("Other Robot"),
-        color: termion::color::AnsiValue::rgb(0, 5, 0),
-        buffer: Vec::new(),
-    };
-
-    term.msg_buffer = Some(msg.clone());
+    let other = speakers.get("other_robot").ok_or("no other_robot speaker")?;
+    term.msg_buffer = Some(other.message());
     let text = "Well, maybe there should be      A SECOND TEST!  dun dun DUNNNN!";
     let char_speed = 150;
     let mut flip = false;
@@ -52,11 +46,11 @@ pub fn term_mock() -> Result<(), Box<Error>> {
         // for c in "HELLO TESTING WORLD!".chars().cycle().take(100) {
         term.msg_buffer.as_mut().unwrap().buffer.push(c);
         if flip {
-            term.user_buffer.push(' ');
-            term.user_buffer.push('x');
+            term.handle_key(Key::Char(' '));
+            term.handle_key(Key::Char('x'));
         } else {
-            term.user_buffer.push('_');
-            term.user_buffer.push('X');
+            term.handle_key(Key::Char('_'));
+            term.handle_key(Key::Char('X'));
         }
         sleep(char_speed as u64);
         term.draw()?;
@@ -64,13 +58,101 @@ pub fn term_mock() -> Result<(), Box<Error>> {
     }
     sleep(50);
     term.msg_done();
-    term.user_msg_done();
-    term.user_buffer.push('!');
+    term.handle_key(Key::Char('\n'));
+    term.handle_key(Key::Char('!'));
     term.draw()?;
     sleep(1000);
 
 
 
+    sleep(1000);
+    term.cleanup()?;
+    Ok(())
+}
+
+// term_mock, but the text is revealed off the audio clock, with the robot's
+// mouth moving as it talks
+pub fn speech_mock() -> Result<(), Box<Error>> {
+    let speakers = Registry::load("data/speakers.toml")?;
+    let robot = speakers.get("robot").ok_or("no robot speaker")?;
+    let mut mix = Mixer::new()?;
+    let mut term = Term::init()?;
+    term.user_name = "Player".to_owned();
+    term.layout = Layout::with_portrait(14);
+    term.portrait = robot.portrait.clone();
+    let mut msg = robot.message();
+    let text = msg.set_markup("Every letter shows up {em}{i}exactly{/i}{/em} when it's \
+                               heard.{pause 300} {speed 0.5}{dim}Neat...{/dim}{/speed}");
+    term.msg_buffer = Some(msg);
+    let (wv, events) = robot.voice.speak_events(&text, &speakers.voices());
+    let mut relay = mix.new_event_stream(wv, events)?;
+    mix.start()?;
+    while mix.is_active()? {
+        for ev in relay.ready(mix.time()) {
+            term.msg_buffer.as_mut().unwrap().buffer.push_str(&ev.text);
+        }
+        term.loudness = mix.level.get();
+        term.draw()?;
+        sleep(5);
+    }
+    mix.close()?;
+    for ev in relay.ready(f64::INFINITY) {
+        term.msg_buffer.as_mut().unwrap().buffer.push_str(&ev.text);
+    }
+    term.msg_done();
+    term.draw()?;
+    sleep(1000);
+    term.cleanup()?;
+    Ok(())
+}
+
+// type into the input line; Esc quits
+pub fn input_mock() -> Result<(), Box<Error>> {
+    let mut term = Term::init()?;
+    term.user_name = "Player".to_owned();
+    let inputs = Inputs::spawn();
+    term.draw()?;
+    loop {
+        for input in inputs.poll() {
+            match input {
+                Input::Key(Key::Esc) => {
+                    term.cleanup()?;
+                    return Ok(());
+                }
+                x => {
+                    term.handle_input(x);
+                }
+            }
+        }
+        term.draw()?;
+        sleep(10);
+    }
+}
+
+// text effects, animating while the line is up and frozen in the log after
+pub fn effects_mock() -> Result<(), Box<Error>> {
+    let speakers = Registry::load("data/speakers.toml")?;
+    let robot = speakers.get("robot").ok_or("no robot speaker")?;
+    let mut term = Term::init()?;
+    let mut msg = robot.message();
+    let text = msg.set_markup("{fade}I am {shake}very angry{/shake} and {wave}quite wobbly{/wave}, \
+                               but {rainbow}fabulous{/rainbow}.{/fade}");
+    term.msg_buffer = Some(msg);
+    for (c, millis) in robot.voice.reveal_marked(&text, &speakers.voices()) {
+        // keep the effects moving while waiting on the next letter
+        for _ in 0..millis / 20 {
+            sleep(20);
+            term.draw()?;
+        }
+        term.msg_buffer.as_mut().unwrap().buffer.push_str(&c);
+        term.draw()?;
+    }
+    for _ in 0..100 {
+        sleep(20);
+        term.draw()?;
+    }
+    term.msg_done();
+    term.draw()?;
     sleep(1000);
     term.cleanup()?;
     Ok(())